@@ -0,0 +1,415 @@
+use crate::client::TokenTide;
+use crate::config::Config;
+use crate::error::TokenTideError;
+use crate::pair::Pair;
+use crate::source::PriceSource;
+use crate::{constants, number};
+use prettytable::{row, Table};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Exit code of `tt alert check` when at least one rule triggered.
+pub const TRIGGERED_EXIT_CODE: i32 = 9;
+
+/// Pair value an alert rule looks at.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    PriceUsd,
+    Change5m,
+    Change1h,
+    Change6h,
+    Change24h,
+    Volume24h,
+    Liquidity,
+    Fdv,
+}
+
+impl Metric {
+    pub const NAMES: [&'static str; 8] = [
+        "price_usd",
+        "change_5m",
+        "change_1h",
+        "change_6h",
+        "change_24h",
+        "volume_24h",
+        "liquidity",
+        "fdv",
+    ];
+
+    /// Reads the metric from a pair. Changes are in percent, the other values
+    /// in USD.
+    pub fn value(&self, pair: &Pair) -> Option<f64> {
+        match self {
+            Metric::PriceUsd => pair
+                .price_usd
+                .as_deref()
+                .and_then(|price| price.parse().ok()),
+            Metric::Change5m => Some(pair.price_change.m5),
+            Metric::Change1h => Some(pair.price_change.h1),
+            Metric::Change6h => Some(pair.price_change.h6),
+            Metric::Change24h => Some(pair.price_change.h24),
+            Metric::Volume24h => Some(pair.volume.h24),
+            Metric::Liquidity => pair.liquidity.as_ref().and_then(|value| value.usd),
+            Metric::Fdv => pair.fdv,
+        }
+    }
+
+    fn is_percent(&self) -> bool {
+        matches!(
+            self,
+            Metric::Change5m | Metric::Change1h | Metric::Change6h | Metric::Change24h
+        )
+    }
+
+    /// Formats a value of this metric for display.
+    pub fn format(&self, value: f64) -> String {
+        match self {
+            Metric::PriceUsd => format!("${}", value),
+            Metric::Change5m | Metric::Change1h | Metric::Change6h | Metric::Change24h => {
+                format!("{:+.2}%", value)
+            }
+            Metric::Volume24h | Metric::Liquidity | Metric::Fdv => {
+                format!("${}", number::format_with_unit(value))
+            }
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "price_usd" | "price" => Ok(Metric::PriceUsd),
+            "change_5m" | "5m" => Ok(Metric::Change5m),
+            "change_1h" | "1h" => Ok(Metric::Change1h),
+            "change_6h" | "6h" => Ok(Metric::Change6h),
+            "change_24h" | "24h" => Ok(Metric::Change24h),
+            "volume_24h" | "volume" => Ok(Metric::Volume24h),
+            "liquidity" | "liquidity_usd" => Ok(Metric::Liquidity),
+            "fdv" => Ok(Metric::Fdv),
+            _ => Err(format!(
+                "Unknown metric: {} (expected one of {})",
+                value,
+                Metric::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Metric::PriceUsd => "price_usd",
+            Metric::Change5m => "change_5m",
+            Metric::Change1h => "change_1h",
+            Metric::Change6h => "change_6h",
+            Metric::Change24h => "change_24h",
+            Metric::Volume24h => "volume_24h",
+            Metric::Liquidity => "liquidity",
+            Metric::Fdv => "fdv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Whether a rule triggers above or below its threshold.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Above,
+    Below,
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "above" | "over" | ">" => Ok(Condition::Above),
+            "below" | "under" | "<" => Ok(Condition::Below),
+            _ => Err(format!(
+                "Unknown condition: {} (expected above or below)",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Above => write!(f, "above"),
+            Condition::Below => write!(f, "below"),
+        }
+    }
+}
+
+/// An alert rule stored in the config, e.g. `HONEY price_usd above 0.25`.
+///
+/// The token is resolved the same way `tt query` resolves it, so it can be a
+/// pinned symbol, a token address or a pair address.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AlertRule {
+    pub token: String,
+    pub metric: Metric,
+    pub condition: Condition,
+    pub threshold: f64,
+}
+
+impl AlertRule {
+    /// Returns whether the rule triggers for the given value.
+    pub fn triggers(&self, value: f64) -> bool {
+        match self.condition {
+            Condition::Above => value > self.threshold,
+            Condition::Below => value < self.threshold,
+        }
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let threshold = if self.metric.is_percent() {
+            format!("{}%", self.threshold)
+        } else {
+            self.threshold.to_string()
+        };
+        write!(
+            f,
+            "{} {} {} {}",
+            self.token.to_uppercase(),
+            self.metric,
+            self.condition,
+            threshold
+        )
+    }
+}
+
+/// Parses a threshold such as `0.25`, `-20%`, `$50k` or `1.5M`.
+pub fn parse_threshold(value: &str) -> std::result::Result<f64, String> {
+    let trimmed = value.trim().trim_start_matches('$').trim_end_matches('%');
+    let (number, multiplier) = match trimmed.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('k') => (&trimmed[..trimmed.len() - 1], 1_000.0),
+        Some('m') => (&trimmed[..trimmed.len() - 1], 1_000_000.0),
+        Some('b') => (&trimmed[..trimmed.len() - 1], 1_000_000_000.0),
+        _ => (trimmed, 1.0),
+    };
+    number
+        .parse::<f64>()
+        .map(|number| number * multiplier)
+        .map_err(|_| format!("Invalid threshold: {}", value))
+}
+
+/// Outcome of evaluating one rule against fresh pair data.
+#[derive(Clone, Debug)]
+pub struct AlertStatus {
+    pub rule: AlertRule,
+    pub pair: String,
+    pub value: Option<f64>,
+    pub triggered: bool,
+    /// Why the rule could not be evaluated, if its pair could not be
+    /// resolved or fetched. Shared, as a pair's error is reported for every
+    /// rule on it.
+    pub error: Option<Arc<TokenTideError>>,
+}
+
+// Serialized flat, so CSV output gets one column per rule field.
+impl Serialize for AlertStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AlertStatus", 8)?;
+        state.serialize_field("token", &self.rule.token)?;
+        state.serialize_field("metric", &self.rule.metric)?;
+        state.serialize_field("condition", &self.rule.condition)?;
+        state.serialize_field("threshold", &self.rule.threshold)?;
+        state.serialize_field("pair", &self.pair)?;
+        state.serialize_field("value", &self.value)?;
+        state.serialize_field("triggered", &self.triggered)?;
+        state.serialize_field("error", &self.error.as_ref().map(|error| error.to_string()))?;
+        state.end()
+    }
+}
+
+/// Evaluates every alert rule in the config against freshly fetched pairs.
+/// Each pair is fetched once, however many rules reference it. A rule whose
+/// pair cannot be resolved or fetched carries the error, and the rest are
+/// still evaluated.
+pub async fn check<S: PriceSource>(client: &TokenTide<S>, config: &Config) -> Vec<AlertStatus> {
+    let mut fetched: HashMap<(String, String), Result<Pair, Arc<TokenTideError>>> = HashMap::new();
+    let mut statuses = vec![];
+    for rule in config.alerts() {
+        let pair = match client.resolve(config, &rule.token).await {
+            Ok(pinned) => match fetched.get(&pinned.key()) {
+                Some(result) => result.clone(),
+                None => {
+                    let result = client.fetch(&pinned).await.map_err(Arc::new);
+                    fetched.insert(pinned.key(), result.clone());
+                    result
+                }
+            },
+            Err(err) => Err(Arc::new(err)),
+        };
+        statuses.push(match pair {
+            Ok(pair) => {
+                let value = rule.metric.value(&pair);
+                AlertStatus {
+                    rule: rule.clone(),
+                    pair: format!("{}{}", pair.base_token.symbol, pair.quote_token.symbol),
+                    value,
+                    triggered: value.is_some_and(|value| rule.triggers(value)),
+                    error: None,
+                }
+            }
+            Err(err) => AlertStatus {
+                rule: rule.clone(),
+                pair: String::new(),
+                value: None,
+                triggered: false,
+                error: Some(err),
+            },
+        });
+    }
+    statuses
+}
+
+/// Builds the table printed by `tt alert list`.
+pub fn rules_table(rules: &[AlertRule]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["#", "Rule"]);
+    for (idx, rule) in rules.iter().enumerate() {
+        table.add_row(row![idx + 1, rule]);
+    }
+    table
+}
+
+/// Builds the table printed by `tt alert check`.
+pub fn statuses_table(statuses: &[AlertStatus]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["Rule", "Pair", "Value", "Triggered"]);
+    for status in statuses {
+        table.add_row(row![
+            status.rule,
+            if status.pair.is_empty() {
+                constants::NONE_STR
+            } else {
+                &status.pair
+            },
+            status
+                .value
+                .map(|value| status.rule.metric.format(value))
+                .unwrap_or(constants::NONE_STR.to_string()),
+            match (&status.error, status.triggered) {
+                (Some(_), _) => "error",
+                (None, true) => "yes",
+                (None, false) => "no",
+            }
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Format;
+    use crate::pair::honey_pair;
+    use crate::source::MockSource;
+
+    #[test]
+    fn test_parse_threshold() {
+        assert_eq!(parse_threshold("0.25"), Ok(0.25));
+        assert_eq!(parse_threshold("-20%"), Ok(-20.0));
+        assert_eq!(parse_threshold("$50k"), Ok(50_000.0));
+        assert_eq!(parse_threshold("1.5M"), Ok(1_500_000.0));
+        assert!(parse_threshold("lots").is_err());
+    }
+
+    #[test]
+    fn test_rule_display_and_triggers() {
+        let rule = AlertRule {
+            token: "honey".to_string(),
+            metric: "24h".parse().unwrap(),
+            condition: "below".parse().unwrap(),
+            threshold: -20.0,
+        };
+        assert_eq!(rule.to_string(), "HONEY change_24h below -20%");
+        assert!(rule.triggers(-25.0));
+        assert!(!rule.triggers(-20.0));
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![honey_pair()],
+        });
+        let config: Config = toml::from_str(
+            r#"
+            pairs = []
+
+            [[alerts]]
+            token = "honey"
+            metric = "price_usd"
+            condition = "above"
+            threshold = 0.1
+
+            [[alerts]]
+            token = "honey"
+            metric = "liquidity"
+            condition = "below"
+            threshold = 50000.0
+            "#,
+        )
+        .unwrap();
+        let statuses = check(&client, &config).await;
+        assert_eq!(statuses.len(), 2);
+        assert!(statuses[0].triggered);
+        assert_eq!(statuses[0].pair, "HONEYUSDC");
+        assert!(!statuses[1].triggered);
+        assert_eq!(statuses[1].value, Some(60480.0));
+
+        let csv = crate::output::render_rows(&statuses, Table::new(), Format::Csv).unwrap();
+        assert_eq!(
+            csv.lines().next(),
+            Some("token,metric,condition,threshold,pair,value,triggered,error")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_keeps_going_past_errors() {
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![honey_pair()],
+        });
+        let config: Config = toml::from_str(
+            r#"
+            pairs = []
+
+            [[alerts]]
+            token = "base:honey"
+            metric = "price_usd"
+            condition = "above"
+            threshold = 0.1
+
+            [[alerts]]
+            token = "honey"
+            metric = "price_usd"
+            condition = "above"
+            threshold = 0.1
+            "#,
+        )
+        .unwrap();
+        let statuses = check(&client, &config).await;
+        assert_eq!(statuses.len(), 2);
+        assert!(matches!(
+            statuses[0].error.as_deref(),
+            Some(TokenTideError::NotFound(_))
+        ));
+        assert!(!statuses[0].triggered);
+        assert!(statuses[1].error.is_none());
+        assert!(statuses[1].triggered);
+        assert!(statuses_table(&statuses).to_string().contains("error"));
+    }
+}
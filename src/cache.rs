@@ -0,0 +1,242 @@
+use crate::config;
+use crate::history::unix_now;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// How long entries are kept past their TTL, for `--offline` to serve.
+const KEEP_FOR_OFFLINE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Cache settings stored in the `[cache]` table of the config.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CacheSettings {
+    /// How long a cached response is served instead of asking the API again.
+    pub ttl_secs: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheSettings { ttl_secs: 30 }
+    }
+}
+
+/// How responses are read from and written to the cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Serve responses younger than the TTL, store fresh ones.
+    Normal,
+    /// Always ask the API, store what it answers.
+    Refresh,
+    /// Neither read nor write the cache.
+    Bypass,
+    /// Never ask the API, serve cached responses however old.
+    Offline,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    fetched_at: u64,
+    body: String,
+}
+
+/// On-disk cache of API responses, one file per URL.
+///
+/// The cache is best effort: entries that cannot be read are misses and
+/// entries that cannot be written are dropped. Entries are written whole
+/// through a renamed temp file, and those older than the TTL plus a week are
+/// removed on the first write of a run.
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+    mode: CacheMode,
+    oldest_served: Mutex<Option<u64>>,
+    pruned: AtomicBool,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf, ttl: Duration, mode: CacheMode) -> Cache {
+        Cache {
+            dir,
+            ttl,
+            mode,
+            oldest_served: Mutex::new(None),
+            pruned: AtomicBool::new(false),
+        }
+    }
+
+    /// The cache kept next to the config file.
+    pub fn beside(config_path: &Path, ttl: Duration, mode: CacheMode) -> Cache {
+        Cache::new(config_path.with_file_name("cache"), ttl, mode)
+    }
+
+    pub fn mode(&self) -> CacheMode {
+        self.mode
+    }
+
+    /// Returns the cached body for a URL if the mode allows serving it.
+    pub fn lookup(&self, url: &str) -> Option<String> {
+        if matches!(self.mode, CacheMode::Refresh | CacheMode::Bypass) {
+            return None;
+        }
+        let text = fs::read_to_string(self.path(url)).ok()?;
+        let entry: Entry = serde_json::from_str(&text).ok()?;
+        if entry.url != url {
+            return None;
+        }
+        let age = unix_now().saturating_sub(entry.fetched_at);
+        if self.mode == CacheMode::Normal && age >= self.ttl.as_secs() {
+            return None;
+        }
+        let mut oldest = self.oldest_served.lock().expect("cache lock");
+        *oldest = Some(oldest.map_or(entry.fetched_at, |at| at.min(entry.fetched_at)));
+        Some(entry.body)
+    }
+
+    /// Stores a fresh body for a URL if the mode allows writing.
+    pub fn store(&self, url: &str, body: &str) {
+        if matches!(self.mode, CacheMode::Bypass | CacheMode::Offline) {
+            return;
+        }
+        let entry = Entry {
+            url: url.to_string(),
+            fetched_at: unix_now(),
+            body: body.to_string(),
+        };
+        if let Ok(text) = serde_json::to_string(&entry) {
+            let _ = fs::create_dir_all(&self.dir);
+            let _ = config::write_atomic(&self.path(url), text.as_bytes());
+        }
+        if !self.pruned.swap(true, Ordering::Relaxed) {
+            self.prune(self.ttl + KEEP_FOR_OFFLINE);
+        }
+    }
+
+    /// Removes the files in the cache directory not written within `max_age`,
+    /// including temp files left by an interrupted write.
+    fn prune(&self, max_age: Duration) {
+        let Ok(files) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for file in files.flatten() {
+            let expired = file
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > max_age));
+            if expired {
+                let _ = fs::remove_file(file.path());
+            }
+        }
+    }
+
+    /// When the oldest response served from the cache was fetched, if any was.
+    pub fn oldest_served(&self) -> Option<u64> {
+        *self.oldest_served.lock().expect("cache lock")
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(url)))
+    }
+}
+
+/// FNV-1a hash, stable across builds unlike the std hasher.
+fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Formats an age in seconds as e.g. `45s`, `12m`, `3h` or `2d`.
+pub fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_modes() {
+        let dir = TempDir::new("cache-modes");
+        let url = "https://api.dexscreener.io/latest/dex/search/?q=honey";
+        let cache = Cache::new(
+            dir.path().to_path_buf(),
+            Duration::from_secs(30),
+            CacheMode::Normal,
+        );
+        assert_eq!(cache.lookup(url), None);
+        cache.store(url, "{}");
+        assert_eq!(cache.lookup(url).as_deref(), Some("{}"));
+        assert!(cache.oldest_served().is_some());
+
+        let expired = Cache::new(dir.path().to_path_buf(), Duration::ZERO, CacheMode::Normal);
+        assert_eq!(expired.lookup(url), None);
+        let offline = Cache::new(dir.path().to_path_buf(), Duration::ZERO, CacheMode::Offline);
+        assert_eq!(offline.lookup(url).as_deref(), Some("{}"));
+        offline.store(url, "[]");
+        let refresh = Cache::new(
+            dir.path().to_path_buf(),
+            Duration::from_secs(30),
+            CacheMode::Refresh,
+        );
+        assert_eq!(refresh.lookup(url), None);
+        refresh.store(url, "[]");
+        assert_eq!(cache.lookup(url).as_deref(), Some("[]"));
+
+        let bypass = Cache::new(
+            dir.path().to_path_buf(),
+            Duration::from_secs(30),
+            CacheMode::Bypass,
+        );
+        assert_eq!(bypass.lookup(url), None);
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = TempDir::new("cache-prune");
+        let cache = Cache::new(
+            dir.path().to_path_buf(),
+            Duration::from_secs(30),
+            CacheMode::Normal,
+        );
+        cache.store("https://host/a", "{}");
+        let stale = dir.join("stale.json");
+        fs::write(&stale, "{}").unwrap();
+        let week_ago = SystemTime::now() - KEEP_FOR_OFFLINE - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(week_ago)
+            .unwrap();
+        // Pruning runs on the first write of a cache, so a new one prunes.
+        let cache = Cache::new(
+            dir.path().to_path_buf(),
+            Duration::from_secs(30),
+            CacheMode::Normal,
+        );
+        cache.store("https://host/b", "{}");
+        assert!(!stale.exists());
+        assert_eq!(cache.lookup("https://host/a").as_deref(), Some("{}"));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(45), "45s");
+        assert_eq!(format_age(12 * 60 + 5), "12m");
+        assert_eq!(format_age(3 * 3600), "3h");
+        assert_eq!(format_age(2 * 86400 + 1), "2d");
+    }
+}
@@ -0,0 +1,270 @@
+use crate::history::{self, Snapshot};
+use crate::number;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// How `tt chart` draws the price line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// One block character per column.
+    Spark,
+    /// A line drawn with braille dots, two by four per character.
+    Braille,
+}
+
+impl Style {
+    pub const NAMES: [&'static str; 2] = ["braille", "spark"];
+}
+
+impl std::str::FromStr for Style {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "braille" => Ok(Style::Braille),
+            "spark" | "sparkline" => Ok(Style::Spark),
+            _ => Err(format!("Unknown chart style: {}", value)),
+        }
+    }
+}
+
+/// Chart dimensions and options.
+#[derive(Clone, Copy, Debug)]
+pub struct ChartOptions {
+    pub style: Style,
+    /// Width of the plot in characters.
+    pub width: usize,
+    /// Height of the braille plot in characters.
+    pub height: usize,
+    /// Adds a row of 24h volume bars below the price.
+    pub volume: bool,
+}
+
+/// Resamples values to `points` values, averaging buckets when shrinking and
+/// repeating the nearest value when stretching.
+pub fn resample(values: &[f64], points: usize) -> Vec<f64> {
+    if values.is_empty() || points == 0 {
+        return vec![];
+    }
+    (0..points)
+        .map(|idx| {
+            if values.len() > points {
+                let start = idx * values.len() / points;
+                let end = ((idx + 1) * values.len() / points).max(start + 1);
+                let bucket = &values[start..end];
+                bucket.iter().sum::<f64>() / bucket.len() as f64
+            } else if points == 1 {
+                values[values.len() - 1]
+            } else {
+                values[idx * (values.len() - 1) / (points - 1)]
+            }
+        })
+        .collect()
+}
+
+fn bounds(values: &[f64]) -> (f64, f64) {
+    values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(*value), max.max(*value))
+        })
+}
+
+/// Scales a value into `0..levels`, putting flat series in the middle.
+fn level(value: f64, min: f64, max: f64, levels: usize) -> usize {
+    if max <= min {
+        return levels / 2;
+    }
+    let scaled = (value - min) / (max - min) * (levels - 1) as f64;
+    (scaled.round() as usize).min(levels - 1)
+}
+
+/// Draws values as a one-line sparkline of the given width.
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    let values = resample(values, width.min(values.len().max(1)).max(1));
+    let (min, max) = bounds(&values);
+    values
+        .iter()
+        .map(|value| SPARKS[level(*value, min, max, SPARKS.len())])
+        .collect()
+}
+
+/// Draws values as a braille line chart of `width` by `height` characters,
+/// returning the rows from top to bottom.
+pub fn braille(values: &[f64], width: usize, height: usize) -> Vec<String> {
+    let columns = width * 2;
+    let rows = height * 4;
+    let values = resample(values, columns);
+    let (min, max) = bounds(&values);
+    let mut dots = vec![vec![false; columns]; rows];
+    let mut previous: Option<usize> = None;
+    for (x, value) in values.iter().enumerate() {
+        let y = rows - 1 - level(*value, min, max, rows);
+        // Fill the gap to the previous point so steep moves stay connected.
+        let (from, to) = match previous {
+            Some(previous) if previous < y => (previous + 1, y),
+            Some(previous) if previous > y => (y, previous - 1),
+            _ => (y, y),
+        };
+        for row in dots.iter_mut().take(to + 1).skip(from) {
+            row[x] = true;
+        }
+        previous = Some(y);
+    }
+
+    const BITS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    (0..height)
+        .map(|char_row| {
+            (0..width)
+                .map(|char_col| {
+                    let mut bits = 0;
+                    for (dy, row_bits) in BITS.iter().enumerate() {
+                        for (dx, bit) in row_bits.iter().enumerate() {
+                            if dots[char_row * 4 + dy][char_col * 2 + dx] {
+                                bits |= bit;
+                            }
+                        }
+                    }
+                    char::from_u32(0x2800 + bits).unwrap_or(' ')
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Renders the price chart of a recorded series with min/max/last
+/// annotations, or `None` if the series has no prices.
+pub fn render(title: &str, snapshots: &[Snapshot], options: ChartOptions) -> Option<String> {
+    let prices: Vec<f64> = snapshots.iter().filter_map(|s| s.price_usd).collect();
+    let (first, last) = (*prices.first()?, *prices.last()?);
+    let (min, max) = bounds(&prices);
+
+    let max_label = format!("max {}", number::format_price(max));
+    let min_label = format!("min {}", number::format_price(min));
+    let gutter = max_label.len().max(min_label.len()).max(3);
+
+    let mut lines = vec![format!("{} ({} snapshots)", title, snapshots.len())];
+    let plot = match options.style {
+        Style::Spark => vec![sparkline(&prices, options.width)],
+        Style::Braille => braille(&prices, options.width, options.height.max(1)),
+    };
+    let last_row = plot.len() - 1;
+    for (idx, row) in plot.iter().enumerate() {
+        let label = if idx == 0 {
+            max_label.as_str()
+        } else if idx == last_row {
+            min_label.as_str()
+        } else {
+            ""
+        };
+        let label = if plot.len() == 1 { "" } else { label };
+        lines.push(format!("{:>gutter$} ┤{}", label, row, gutter = gutter));
+    }
+    if options.volume {
+        let volumes: Vec<f64> = snapshots.iter().map(|s| s.volume_h24).collect();
+        lines.push(format!(
+            "{:>gutter$} ┤{}",
+            "vol",
+            sparkline(&volumes, options.width),
+            gutter = gutter
+        ));
+    }
+    if let (Some(start), Some(end)) = (snapshots.first(), snapshots.last()) {
+        let start = history::format_time(start.timestamp);
+        let end = history::format_time(end.timestamp);
+        let padding = (options.width + 1)
+            .saturating_sub(start.len() + end.len())
+            .max(1);
+        lines.push(format!(
+            "{:>gutter$}  {}{}{}",
+            "",
+            start,
+            " ".repeat(padding),
+            end,
+            gutter = gutter
+        ));
+    }
+    let change = if first > 0.0 {
+        format!(" ({:+.2}%)", (last - first) / first * 100.0)
+    } else {
+        String::new()
+    };
+    let mut summary = format!(
+        "last {}{}  min {}  max {}",
+        number::format_price(last),
+        change,
+        number::format_price(min),
+        number::format_price(max)
+    );
+    if options.volume {
+        if let Some(snapshot) = snapshots.last() {
+            summary.push_str(&format!(
+                "  24h volume {}",
+                number::format_with_unit(snapshot.volume_h24)
+            ));
+        }
+    }
+    lines.push(summary);
+    Some(lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+
+    #[test]
+    fn test_resample() {
+        assert_eq!(resample(&[1.0, 3.0, 5.0, 7.0], 2), vec![2.0, 6.0]);
+        assert_eq!(resample(&[1.0, 2.0], 4), vec![1.0, 1.0, 1.0, 2.0]);
+        assert!(resample(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(
+            sparkline(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0], 8),
+            "▁▂▃▄▅▆▇█"
+        );
+        assert_eq!(sparkline(&[2.0, 2.0], 10), "▅▅");
+    }
+
+    #[test]
+    fn test_braille_diagonal() {
+        let rows = braille(&[0.0, 1.0, 2.0, 3.0], 1, 1);
+        // Rising line: the bottom-left dot, then the right column filled up
+        // to the top.
+        assert_eq!(rows, vec!["⡸".to_string()]);
+        let rows = braille(&[1.0, 5.0, 2.0], 20, 5);
+        assert_eq!(rows.len(), 5);
+        assert!(rows.iter().all(|row| row.chars().count() == 20));
+    }
+
+    #[test]
+    fn test_render_annotations() {
+        let pair = honey_pair();
+        let mut snapshots = vec![];
+        for (idx, price) in [0.1, 0.2, 0.15].iter().enumerate() {
+            let mut snapshot = Snapshot::new(&pair, 1_700_000_000 + idx as u64 * 60);
+            snapshot.price_usd = Some(*price);
+            snapshots.push(snapshot);
+        }
+        let options = ChartOptions {
+            style: Style::Braille,
+            width: 30,
+            height: 4,
+            volume: true,
+        };
+        let chart = render("HONEYUSDC", &snapshots, options).unwrap();
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines[0], "HONEYUSDC (3 snapshots)");
+        assert!(lines[1].starts_with("max 0.2000 ┤"));
+        assert!(lines[4].starts_with("min 0.1000 ┤"));
+        assert!(lines[5].trim_start().starts_with("vol ┤"));
+        assert_eq!(
+            lines.last().unwrap(),
+            &"last 0.1500 (+50.00%)  min 0.1000  max 0.2000  24h volume 249.16K"
+        );
+        assert!(render("empty", &[], options).is_none());
+    }
+}
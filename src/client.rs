@@ -0,0 +1,396 @@
+use crate::config::{Config, PairConfig};
+use crate::dexscreener::DexScreener;
+use crate::error::{Result, TokenTideError};
+use crate::history::History;
+use crate::pair::Pair;
+use crate::pins;
+use crate::source::PriceSource;
+use futures::{future, stream, StreamExt};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Result of pinning a pair with [`TokenTide::add`].
+#[derive(Clone, Debug)]
+pub struct AddOutcome {
+    /// The pair that was pinned for the same symbol before, if any.
+    pub replaced: Option<PairConfig>,
+    /// The pair that is pinned now.
+    pub added: PairConfig,
+}
+
+/// Client for looking up token prices, backed by a [`PriceSource`].
+///
+/// Lookups consult the pinned pairs of a [`Config`] first, so a symbol keeps
+/// resolving to the same pool once it has been queried or added.
+pub struct TokenTide<S: PriceSource = DexScreener> {
+    source: S,
+    history: Option<History>,
+    /// Whether a failed history append was reported, so it is reported once.
+    history_failed: AtomicBool,
+}
+
+impl TokenTide<DexScreener> {
+    /// Creates a client backed by the DexScreener API.
+    pub fn new() -> TokenTide<DexScreener> {
+        TokenTide::with_source(DexScreener::new())
+    }
+}
+
+impl Default for TokenTide<DexScreener> {
+    fn default() -> Self {
+        TokenTide::new()
+    }
+}
+
+impl<S: PriceSource> TokenTide<S> {
+    /// Creates a client backed by the given source.
+    pub fn with_source(source: S) -> TokenTide<S> {
+        TokenTide {
+            source,
+            history: None,
+            history_failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Records a snapshot of every fetched pair in the given history.
+    pub fn with_history(mut self, history: History) -> TokenTide<S> {
+        self.history = Some(history);
+        self
+    }
+
+    /// Returns the underlying price source.
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Searches every pair matching a symbol or address.
+    pub async fn search(&self, token: &str) -> Result<Vec<Pair>> {
+        self.source.search(token).await
+    }
+
+    /// Resolves a search term to a pair, preferring the pairs pinned in the
+    /// config and falling back to the first search result of the source. A
+    /// `chain:` prefix, as in `solana:HONEY`, keeps to pairs on that chain.
+    pub async fn resolve(&self, config: &Config, search: &str) -> Result<PairConfig> {
+        if let Some(token) = config.search_token(search)? {
+            return Ok(token);
+        }
+        self.first_result(search).await
+    }
+
+    /// The first search result of the source, on the chain named by a
+    /// `chain:` prefix if there is one.
+    async fn first_result(&self, search: &str) -> Result<PairConfig> {
+        let (chain_id, term) = pins::split_chain(search);
+        let pairs = self.source.search(term).await?;
+        pairs
+            .into_iter()
+            .find(|pair| {
+                chain_id.is_none_or(|chain_id| pair.chain_id.eq_ignore_ascii_case(chain_id))
+            })
+            .map(PairConfig::from)
+            .ok_or_else(|| TokenTideError::NotFound(search.to_string()))
+    }
+
+    /// Fetches fresh data for a resolved pair, recording it in the history
+    /// if one is attached.
+    pub async fn fetch(&self, pair: &PairConfig) -> Result<Pair> {
+        let pairs = self
+            .source
+            .fetch_pair(pair.chain_id.as_str(), pair.pair_address.as_str())
+            .await?;
+        let found = pairs
+            .into_iter()
+            .next()
+            .ok_or_else(|| TokenTideError::NotFound(pair.pair_address.clone()))?;
+        self.record(&found);
+        Ok(found)
+    }
+
+    /// Records a fetched pair in the history, unless the source served it
+    /// from its cache, as then it is not new. The history is kept on the
+    /// side, so failing to append to it is a warning on stderr rather than
+    /// an error for the fetch.
+    fn record(&self, pair: &Pair) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        if self.source.served_from_cache(pair) {
+            return;
+        }
+        if let Err(err) = history.record(pair) {
+            if !self.history_failed.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "Warning: prices are not recorded in {}: {}",
+                    history.path().display(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// Fetches fresh data for many resolved pairs in as few requests as the
+    /// source allows, one batch per chain, recording them like
+    /// [`TokenTide::fetch`]. Results are in input order; a pair the source did
+    /// not return is [`TokenTideError::NotFound`].
+    pub async fn fetch_many(&self, pairs: &[PairConfig]) -> Result<Vec<Result<Pair>>> {
+        let mut chains: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for pair in pairs {
+            let addresses = chains.entry(pair.chain_id.as_str()).or_default();
+            if !addresses.contains(&pair.pair_address) {
+                addresses.push(pair.pair_address.clone());
+            }
+        }
+        let batches = future::try_join_all(
+            chains
+                .iter()
+                .map(|(chain_id, addresses)| self.source.fetch_pairs(chain_id, addresses)),
+        )
+        .await?;
+        // Keyed by chain too, as the same address can be a pair on several chains.
+        let mut found: HashMap<(String, String), Pair> = HashMap::new();
+        for (chain_id, batch) in chains.keys().zip(batches) {
+            for pair in batch {
+                self.record(&pair);
+                found.insert(
+                    (chain_id.to_string(), pair.pair_address.to_uppercase()),
+                    pair,
+                );
+            }
+        }
+        Ok(pairs
+            .iter()
+            .map(|pair| {
+                found
+                    .get(&(pair.chain_id.clone(), pair.pair_address.to_uppercase()))
+                    .cloned()
+                    .ok_or_else(|| TokenTideError::NotFound(pair.pair_address.clone()))
+            })
+            .collect())
+    }
+
+    /// Fetches many resolved pairs with [`TokenTide::fetch_many`]. If a batch
+    /// fails, the pairs are fetched on their own, at most `jobs` at a time, so
+    /// only the pairs that fail by themselves carry an error. Results are in
+    /// input order.
+    pub async fn fetch_each(&self, pairs: &[PairConfig], jobs: usize) -> Vec<Result<Pair>> {
+        match self.fetch_many(pairs).await {
+            Ok(fetched) => fetched,
+            Err(_) => {
+                stream::iter(pairs)
+                    .map(|pair| self.fetch(pair))
+                    .buffered(jobs.max(1))
+                    .collect()
+                    .await
+            }
+        }
+    }
+
+    /// Resolves many search terms, at most `jobs` at a time, then fetches the
+    /// resolved pairs with [`TokenTide::fetch_each`]. Results are in input
+    /// order.
+    pub async fn resolve_many(
+        &self,
+        config: &Config,
+        searches: &[String],
+        jobs: usize,
+    ) -> Vec<Result<Pair>> {
+        let resolved: Vec<Result<PairConfig>> = stream::iter(searches)
+            .map(|search| self.resolve(config, search))
+            .buffered(jobs.max(1))
+            .collect()
+            .await;
+        let pinned: Vec<PairConfig> = resolved
+            .iter()
+            .filter_map(|pair| pair.as_ref().ok().cloned())
+            .collect();
+        let mut fetched = self.fetch_each(&pinned, jobs).await.into_iter();
+        resolved
+            .into_iter()
+            .map(|pair| pair.and_then(|_| fetched.next().expect("one result per pair")))
+            .collect()
+    }
+
+    /// Queries many symbols or addresses concurrently, like [`TokenTide::query`],
+    /// and pins every pair found.
+    pub async fn query_many(
+        &self,
+        config: &mut Config,
+        searches: &[String],
+        jobs: usize,
+    ) -> Result<Vec<Result<Pair>>> {
+        let results = self.resolve_many(config, searches, jobs).await;
+        for pair in results.iter().flatten() {
+            config.append_token(PairConfig::from(pair.clone()))?;
+        }
+        Ok(results)
+    }
+
+    /// Queries the current pair for a symbol or address and pins it in the
+    /// config.
+    pub async fn query(&self, config: &mut Config, search: &str) -> Result<Pair> {
+        let pair = self.resolve(config, search).await?;
+        let found = self.fetch(&pair).await?;
+        config.append_token(PairConfig::from(found.clone()))?;
+        Ok(found)
+    }
+
+    /// Pins the first search result for a token or pair address, optionally
+    /// prefixed by its chain as in `solana:HONEY`, replacing the pair
+    /// previously pinned for the same token on that chain.
+    pub async fn add(&self, config: &mut Config, search: &str) -> Result<AddOutcome> {
+        let pair = self.first_result(search).await?;
+        let found = self.fetch(&pair).await?;
+        let added = PairConfig::from(found);
+        let replaced = config.replace_token(added.clone())?;
+        Ok(AddOutcome { replaced, added })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+    use crate::source::MockSource;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn test_resolve_from_source() {
+        let source = MockSource {
+            pairs: vec![honey_pair()],
+        };
+        let client = TokenTide::with_source(source);
+        let config = Config::default();
+        let pair = client.resolve(&config, "honey").await.unwrap();
+        assert_eq!(pair.base_token_symbol, "HONEY");
+        assert_eq!(
+            pair.pair_address,
+            "2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_not_found() {
+        let source = MockSource { pairs: vec![] };
+        let client = TokenTide::with_source(source);
+        let config = Config::default();
+        let err = client.resolve(&config, "honey").await.unwrap_err();
+        assert!(matches!(err, TokenTideError::NotFound(_)));
+        assert_eq!(err.exit_code(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_past_history_failure() {
+        // Nothing can be created under a file, so every append fails.
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![honey_pair()],
+        })
+        .with_history(History::new(PathBuf::from("/dev/null/history.jsonl")));
+        let pinned = PairConfig::from(honey_pair());
+        assert_eq!(
+            client.fetch(&pinned).await.unwrap().base_token.symbol,
+            "HONEY"
+        );
+        let results = client.fetch_many(&[pinned]).await.unwrap();
+        assert!(results[0].is_ok());
+        assert!(client.history_failed.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_add_by_chain() {
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![honey_pair()],
+        });
+        let mut config = Config::default();
+        let err = client.add(&mut config, "base:honey").await.unwrap_err();
+        assert!(matches!(err, TokenTideError::NotFound(_)));
+        assert!(config.pairs().is_empty());
+        let outcome = client.add(&mut config, "Solana:honey").await.unwrap();
+        assert_eq!(outcome.added.chain_id, "solana");
+        assert_eq!(config.pairs().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_many() {
+        let honey = honey_pair();
+        let mut bonk = honey.clone();
+        bonk.pair_address = "bonk_pair".to_string();
+        bonk.base_token.symbol = "BONK".to_string();
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![honey.clone(), bonk.clone()],
+        });
+        let config: Config = toml::from_str(
+            r#"
+            [[pairs]]
+            chain_id = "solana"
+            base_token_symbol = "BONK"
+            quote_token_symbol = "SOL"
+            base_token_address = "bonk_address"
+            pair_address = "bonk_pair"
+
+            [[pairs]]
+            chain_id = "solana"
+            base_token_symbol = "GONE"
+            quote_token_symbol = "SOL"
+            base_token_address = "gone_address"
+            pair_address = "gone_pair"
+            "#,
+        )
+        .unwrap();
+        let searches: Vec<String> = ["bonk", "honey", "gone", "bonk"]
+            .iter()
+            .map(|search| search.to_string())
+            .collect();
+        let results = client.resolve_many(&config, &searches, 2).await;
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().base_token.symbol, "BONK");
+        // Unpinned, resolved through the first search result.
+        assert_eq!(results[1].as_ref().unwrap().base_token.symbol, "HONEY");
+        assert!(matches!(results[2], Err(TokenTideError::NotFound(_))));
+        assert_eq!(results[3].as_ref().unwrap().pair_address, "bonk_pair");
+    }
+
+    /// Serves honey and bonk like [`MockSource`], but fails to fetch bonk.
+    struct FailingBonk(MockSource);
+
+    #[async_trait::async_trait]
+    impl PriceSource for FailingBonk {
+        async fn search(&self, query: &str) -> Result<Vec<Pair>> {
+            self.0.search(query).await
+        }
+
+        async fn fetch_pair(&self, chain_id: &str, pair_address: &str) -> Result<Vec<Pair>> {
+            if pair_address == "bonk_pair" {
+                return Err(TokenTideError::HttpStatus(
+                    reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+                ));
+            }
+            self.0.fetch_pair(chain_id, pair_address).await
+        }
+
+        async fn fetch_token_pairs(&self, token_address: &str) -> Result<Vec<Pair>> {
+            self.0.fetch_token_pairs(token_address).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_many_failed_batch() {
+        let honey = honey_pair();
+        let mut bonk = honey.clone();
+        bonk.pair_address = "bonk_pair".to_string();
+        bonk.base_token.symbol = "BONK".to_string();
+        let client = TokenTide::with_source(FailingBonk(MockSource {
+            pairs: vec![honey.clone(), bonk.clone()],
+        }));
+        let mut config = Config::default();
+        config.append_token(PairConfig::from(bonk)).unwrap();
+        let searches: Vec<String> = ["bonk", "honey"]
+            .iter()
+            .map(|search| search.to_string())
+            .collect();
+        // Both pairs are on solana, so they share a batch that fails for bonk.
+        let results = client.resolve_many(&config, &searches, 2).await;
+        assert!(matches!(results[0], Err(TokenTideError::HttpStatus(_))));
+        assert_eq!(results[1].as_ref().unwrap().base_token.symbol, "HONEY");
+    }
+}
@@ -1,9 +1,47 @@
-use clap::{arg, Command};
+use clap::builder::PossibleValuesParser;
+use clap::{arg, value_parser, Command};
+use std::path::PathBuf;
+use token_tide::alert::{self, Condition, Metric};
+use token_tide::chart;
+use token_tide::history;
+use token_tide::journal;
+use token_tide::output::Format;
+use token_tide::pnl;
+use token_tide::portfolio;
 
 pub fn tt_command() -> Command {
     Command::new("Token Tide")
         .about("Your swift navigator token prices.")
         .subcommand_required(true)
+        .arg(
+            arg!(--format <FORMAT> "Output format")
+                .global(true)
+                .value_parser(PossibleValuesParser::new(Format::NAMES))
+                .default_value("table"),
+        )
+        .arg(
+            arg!(--config <PATH> "Config file, instead of $TOKEN_TIDE_CONFIG or the XDG location")
+                .global(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--profile <NAME> "Profile whose pins and alerts to use, instead of $TOKEN_TIDE_PROFILE")
+                .global(true),
+        )
+        .arg(
+            arg!(--"no-cache" "Neither read nor write cached API responses")
+                .global(true)
+                .conflicts_with_all(["refresh", "offline"]),
+        )
+        .arg(
+            arg!(--refresh "Ignore cached API responses, caching fresh ones")
+                .global(true)
+                .conflicts_with("offline"),
+        )
+        .arg(
+            arg!(--offline "Serve cached API responses however old, without the network")
+                .global(true),
+        )
         .subcommand(
             Command::new("list")
                 .arg(arg!(<TOKEN> "Query list tokens by symbol or address"))
@@ -12,12 +50,244 @@ pub fn tt_command() -> Command {
         )
         .subcommand(
             Command::new("query")
-                .arg(arg!(<TOKEN> "Query token info"))
+                .arg(arg!([TOKEN]... "Query token info, read from stdin if none or `-` is given"))
                 .arg(arg!(--simple "Query token price by symbol or address"))
-                .arg_required_else_help(true),
+                .arg(
+                    arg!(--jobs <N> "How many tokens to resolve at a time")
+                        .value_parser(value_parser!(u64).range(1..=32))
+                        .default_value("4"),
+                ),
         )
         .subcommand(
             Command::new("add")
                 .arg(arg!(<ADDRESS> "Priority query address(When searching for related tokens)")),
         )
+        .subcommand(
+            Command::new("watch")
+                .about("Refresh prices on an interval")
+                .arg(arg!([TOKEN] ... "Tokens to watch, all pinned pairs when omitted"))
+                .arg(
+                    arg!(--interval <SECONDS> "Seconds between refreshes")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .default_value("10"),
+                ),
+        )
+        .subcommand(
+            Command::new("dash")
+                .about("Interactive dashboard of the pinned pairs")
+                .arg(
+                    arg!(--interval <SECONDS> "Seconds between refreshes")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .default_value("30"),
+                ),
+        )
+        .subcommand(
+            Command::new("alert")
+                .about("Manage and check price alert rules")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a rule, e.g. `tt alert add HONEY price_usd above 0.25`")
+                        .arg(arg!(<TOKEN> "Symbol, token address or pair address"))
+                        .arg(
+                            arg!(<METRIC> "Pair value to watch")
+                                .value_parser(|value: &str| value.parse::<Metric>())
+                                .long_help(format!("One of {}", Metric::NAMES.join(", "))),
+                        )
+                        .arg(
+                            arg!(<CONDITION> "above or below")
+                                .value_parser(|value: &str| value.parse::<Condition>()),
+                        )
+                        .arg(
+                            arg!(<THRESHOLD> "Threshold, e.g. 0.25, -20%, $50k")
+                                .value_parser(alert::parse_threshold)
+                                .allow_hyphen_values(true),
+                        ),
+                )
+                .subcommand(Command::new("list").about("List the rules"))
+                .subcommand(
+                    Command::new("remove").about("Remove a rule").arg(
+                        arg!(<INDEX> "Rule number shown by `tt alert list`")
+                            .value_parser(value_parser!(u64).range(1..)),
+                    ),
+                )
+                .subcommand(
+                    Command::new("check")
+                        .about("Evaluate the rules, exiting with code 9 if any triggered")
+                        .arg(arg!(--all "Print every rule, not only triggered ones"))
+                        .arg(arg!(--notify "Send triggered rules to the configured notifiers")),
+                ),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("Print the prices recorded for a token")
+                .arg(arg!(<TOKEN> "Symbol, token address or pair address"))
+                .arg(
+                    arg!(--since <DURATION> "How far back to go, e.g. 12h, 7d, 2w")
+                        .value_parser(history::parse_duration)
+                        .default_value("7d"),
+                ),
+        )
+        .subcommand(
+            Command::new("chart")
+                .about("Chart the prices recorded for a token")
+                .arg(arg!(<TOKEN> "Symbol, token address or pair address"))
+                .arg(
+                    arg!(--since <DURATION> "How far back to go, e.g. 12h, 7d, 2w")
+                        .value_parser(history::parse_duration)
+                        .default_value("24h"),
+                )
+                .arg(
+                    arg!(--style <STYLE> "Line style")
+                        .value_parser(PossibleValuesParser::new(chart::Style::NAMES))
+                        .default_value("braille"),
+                )
+                .arg(
+                    arg!(--width <COLUMNS> "Chart width in characters")
+                        .value_parser(value_parser!(u64).range(2..))
+                        .default_value("60"),
+                )
+                .arg(
+                    arg!(--height <ROWS> "Chart height in characters")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .default_value("10"),
+                )
+                .arg(arg!(--volume "Add 24h volume bars")),
+        )
+        .subcommand(
+            Command::new("portfolio")
+                .about("Track token holdings, their value and profit and loss")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add an amount of a token, e.g. `tt portfolio add HONEY 1000`")
+                        .arg(arg!(<TOKEN> "Symbol, token address or pair address"))
+                        .arg(
+                            arg!(<AMOUNT> "Amount of the token held")
+                                .value_parser(portfolio::parse_amount),
+                        )
+                        .arg(
+                            arg!(--cost <USD> "Total USD paid for the amount")
+                                .value_parser(portfolio::parse_cost),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove a holding")
+                        .arg(arg!(<TOKEN> "Symbol, token address or pair address")),
+                )
+                .subcommand(Command::new("show").about("Value the holdings at current prices"))
+                .subcommand(trade("buy", "Record a buy, adding to the holding"))
+                .subcommand(trade("sell", "Record a sell, reducing the holding"))
+                .subcommand(Command::new("transactions").about("List the recorded buys and sells"))
+                .subcommand(
+                    Command::new("pnl")
+                        .about("Show realized and unrealized profit and loss per token")
+                        .arg(
+                            arg!(--method <METHOD> "How sells are matched against buys")
+                                .value_parser(PossibleValuesParser::new(pnl::Method::NAMES))
+                                .default_value("fifo"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Export the transactions and recorded prices as a journal")
+                        .arg(
+                            arg!(<JOURNAL> "Plain-text accounting syntax")
+                                .value_parser(PossibleValuesParser::new(journal::Journal::NAMES)),
+                        )
+                        .arg(
+                            arg!(--method <METHOD> "How sells are matched against lots")
+                                .value_parser(PossibleValuesParser::new(["fifo", "lifo"]))
+                                .default_value("fifo"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("pins")
+                .about("Manage the pinned pairs lookups resolve to")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List the pinned pairs"))
+                .subcommand(
+                    Command::new("remove")
+                        .about("Unpin a pair")
+                        .arg(arg!(<PIN> "Number shown by `tt pins list`, symbol, alias or address"))
+                        .arg(arg!(-y --yes "Do not ask for confirmation")),
+                )
+                .subcommand(
+                    Command::new("clear")
+                        .about("Unpin every pair")
+                        .arg(arg!(-y --yes "Do not ask for confirmation")),
+                )
+                .subcommand(
+                    Command::new("move")
+                        .about("Move a pinned pair to another position")
+                        .arg(arg!(<PIN> "Number shown by `tt pins list`, symbol, alias or address"))
+                        .arg(
+                            arg!(<POSITION> "New position, 1 being the top")
+                                .value_parser(value_parser!(u64).range(1..)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("rename")
+                        .about("Set the alias a pinned pair can be looked up by")
+                        .arg(arg!(<PIN> "Number shown by `tt pins list`, symbol, alias or address"))
+                        .arg(arg!([ALIAS] "New alias, the alias is removed when omitted")),
+                ),
+        )
+        .subcommand(
+            Command::new("profile")
+                .about("Manage the profiles picked with --profile")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List the profiles"))
+                .subcommand(
+                    Command::new("add")
+                        .about("Add an empty profile")
+                        .arg(arg!(<NAME> "Profile name"))
+                        .arg(
+                            arg!(--"default-format" <FORMAT> "Output format when --format is not given")
+                                .value_parser(PossibleValuesParser::new(Format::NAMES)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("format")
+                        .about("Set the output format used when --format is not given")
+                        .arg(arg!(<NAME> "Profile name, `default` for the top-level lists"))
+                        .arg(
+                            arg!([FORMAT] "Output format, the setting is removed when omitted")
+                                .value_parser(PossibleValuesParser::new(Format::NAMES)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove a profile with its pins and alerts")
+                        .arg(arg!(<NAME> "Profile name"))
+                        .arg(arg!(-y --yes "Do not ask for confirmation")),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Inspect the config file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("doctor")
+                        .about("Check the config file for problems")
+                        .arg(arg!(--fix "Back the file up and rewrite it without the problems")),
+                ),
+        )
+}
+
+fn trade(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(arg!(<TOKEN> "Symbol, token address or pair address"))
+        .arg(arg!(<AMOUNT> "Amount of the token").value_parser(portfolio::parse_amount))
+        .arg(
+            arg!(--price <USD> "USD price per token, defaults to the current price")
+                .value_parser(pnl::parse_price),
+        )
+        .arg(
+            arg!(--date <DATE> "Trade date as YYYY-MM-DD, defaults to today")
+                .value_parser(pnl::parse_date),
+        )
 }
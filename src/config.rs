@@ -1,20 +1,56 @@
-use crate::dexscreener::Pair;
+use crate::alert::AlertRule;
+use crate::cache::CacheSettings;
+use crate::doctor;
+use crate::error::{Result, TokenTideError};
+use crate::history::unix_now;
+use crate::http::HttpSettings;
+use crate::migrate;
+use crate::notify::NotifierConfig;
+use crate::output::Format;
+use crate::pair::Pair;
+use crate::pins;
+use crate::pnl::{self, Transaction};
+use crate::portfolio::Holding;
+use crate::profile::{self, Profile};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
 use std::fs;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Environment variable naming the config file, overriding the XDG location.
+pub const CONFIG_ENV: &str = "TOKEN_TIDE_CONFIG";
+
+/// A pair pinned in the config, so lookups for its symbol or addresses keep
+/// resolving to the same pool.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct PairConfig {
     pub chain_id: String,
     pub base_token_symbol: String,
     pub quote_token_symbol: String,
     pub base_token_address: String,
     pub pair_address: String,
+    /// The DEX the pair trades on. No migration fills it in, as the config
+    /// does not record it, so pairs pinned by an older `tt` have none until
+    /// they are pinned again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dex_id: Option<String>,
+    /// When the pair was pinned, as unix seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_at: Option<u64>,
+    /// Another name the pair can be looked up by, set with `tt pins rename`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
 }
 
 impl PairConfig {
+    /// Builds the pinned form of a fetched pair.
     pub fn from(pair: Pair) -> PairConfig {
         PairConfig {
             chain_id: pair.chain_id,
@@ -22,147 +58,741 @@ impl PairConfig {
             quote_token_symbol: pair.quote_token.symbol,
             base_token_address: pair.base_token.address,
             pair_address: pair.pair_address,
+            dex_id: Some(pair.dex_id),
+            added_at: Some(unix_now()),
+            alias: None,
         }
     }
+
+    /// Identifies the pair across chains, as the same address can be a pair
+    /// on several of them.
+    pub fn key(&self) -> (String, String) {
+        (
+            self.chain_id.to_uppercase(),
+            self.pair_address.to_uppercase(),
+        )
+    }
+
+    /// Whether this is the pair with the given address on the given chain.
+    pub fn is_pair(&self, chain_id: &str, pair_address: &str) -> bool {
+        self.chain_id.eq_ignore_ascii_case(chain_id)
+            && self.pair_address.eq_ignore_ascii_case(pair_address)
+    }
 }
 
+/// The token-tide config, stored at `$XDG_CONFIG_HOME/token-tide/config.toml`
+/// (`~/.config/token-tide/config.toml` by default).
+///
+/// A config remembers the file it was loaded from and saves changes back to
+/// it. [`Config::default`] is not backed by a file and never writes.
+///
+/// Saving is safe with several `tt` processes sharing the file: each change
+/// is made holding an advisory lock on `config.toml.lock`, on top of the file
+/// as another process may have saved it since it was loaded, and written to a
+/// temp file renamed over the config, so the file is never half written.
+///
+/// Files written for an older schema [`version`](migrate::CURRENT_VERSION)
+/// are upgraded when loaded, after a backup next to them.
+///
+/// Pinned pairs and alert rules are those of the [`Profile`] picked with
+/// [`Config::use_profile`], the top-level lists unless one is picked.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
+    #[serde(skip)]
+    file: Option<PathBuf>,
+    /// The file's text as last read or written, to notice other processes' saves.
+    #[serde(skip)]
+    synced: Option<String>,
+    /// The profile in use, `None` for the top-level lists.
+    #[serde(skip)]
+    profile: Option<String>,
+    /// Files are checked for it before deserializing, see [`Config::load_from`].
+    #[serde(default = "current_version")]
+    version: i64,
+    /// The output format used when `--format` is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    format: Option<Format>,
     pairs: Vec<PairConfig>,
+    #[serde(default)]
+    alerts: Vec<AlertRule>,
+    #[serde(default)]
+    notifiers: Vec<NotifierConfig>,
+    #[serde(default)]
+    holdings: Vec<Holding>,
+    #[serde(default)]
+    transactions: Vec<Transaction>,
+    #[serde(default)]
+    http: HttpSettings,
+    #[serde(default)]
+    cache: CacheSettings,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            file: None,
+            synced: None,
+            profile: None,
+            version: migrate::CURRENT_VERSION,
+            format: None,
+            pairs: vec![],
+            alerts: vec![],
+            notifiers: vec![],
+            holdings: vec![],
+            transactions: vec![],
+            http: HttpSettings::default(),
+            cache: CacheSettings::default(),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+fn current_version() -> i64 {
+    migrate::CURRENT_VERSION
+}
+
+/// Just the schema version of a config file.
+#[derive(Deserialize)]
+struct Versioned {
+    version: Option<i64>,
 }
 
 impl Config {
-    pub fn clear(&mut self) {
-        self.pairs.clear();
-        self.save()
+    /// Returns the pinned pairs in the order they were added.
+    pub fn pairs(&self) -> &[PairConfig] {
+        match &self.profile {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .map(|profile| profile.pairs.as_slice())
+                .unwrap_or_default(),
+            None => &self.pairs,
+        }
+    }
+
+    /// The pinned pairs and alert rules of the profile in use. A profile
+    /// another process removed since it was picked is made again.
+    fn lists_mut(&mut self) -> (&mut Vec<PairConfig>, &mut Vec<AlertRule>) {
+        match &self.profile {
+            Some(name) => {
+                let profile = self.profiles.entry(name.clone()).or_default();
+                (&mut profile.pairs, &mut profile.alerts)
+            }
+            None => (&mut self.pairs, &mut self.alerts),
+        }
+    }
+
+    fn pairs_mut(&mut self) -> &mut Vec<PairConfig> {
+        self.lists_mut().0
+    }
+
+    /// Returns the name of the profile in use, `None` for the top-level lists.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Uses the named profile's pairs and alert rules from now on, or the
+    /// top-level ones for `default`. Fails if there is no such profile.
+    pub fn use_profile(&mut self, name: &str) -> Result<()> {
+        if name == profile::DEFAULT_NAME {
+            self.profile = None;
+            return Ok(());
+        }
+        if !self.profiles.contains_key(name) {
+            return Err(TokenTideError::NotFound(format!("profile {}", name)));
+        }
+        self.profile = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Returns the output format of the profile in use, falling back to the
+    /// top-level one.
+    pub fn format(&self) -> Option<Format> {
+        self.profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+            .and_then(|profile| profile.format)
+            .or(self.format)
     }
+
+    /// Returns the top-level lists as the `default` profile.
+    pub fn default_profile(&self) -> Profile {
+        Profile {
+            format: self.format,
+            pairs: self.pairs.clone(),
+            alerts: self.alerts.clone(),
+        }
+    }
+
+    /// Returns the named profiles.
+    pub fn profiles(&self) -> &BTreeMap<String, Profile> {
+        &self.profiles
+    }
+
+    /// Adds an empty profile with the given output format and saves the
+    /// config. Fails if the profile exists.
+    pub fn add_profile(&mut self, name: &str, format: Option<Format>) -> Result<()> {
+        let name = profile::validate_name(name)?;
+        self.update(|config| {
+            if config.profiles.contains_key(&name) {
+                return Err(TokenTideError::Invalid(format!(
+                    "profile {} already exists",
+                    name
+                )));
+            }
+            config.profiles.insert(
+                name,
+                Profile {
+                    format,
+                    ..Profile::default()
+                },
+            );
+            Ok(())
+        })
+    }
+
+    /// Sets or, given `None`, removes the output format of the named profile,
+    /// `default` naming the top-level one, and saves the config.
+    pub fn set_profile_format(&mut self, name: &str, format: Option<Format>) -> Result<()> {
+        self.update(|config| {
+            if name == profile::DEFAULT_NAME {
+                config.format = format;
+                return Ok(());
+            }
+            match config.profiles.get_mut(name) {
+                Some(profile) => {
+                    profile.format = format;
+                    Ok(())
+                }
+                None => Err(TokenTideError::NotFound(format!("profile {}", name))),
+            }
+        })
+    }
+
+    /// Removes the named profile with its pairs and alert rules and saves the
+    /// config, returning it.
+    pub fn remove_profile(&mut self, name: &str) -> Result<Option<Profile>> {
+        self.update(|config| Ok(config.profiles.remove(name)))
+    }
+
+    /// Removes every pinned pair and saves the config.
+    pub fn clear(&mut self) -> Result<()> {
+        self.update(|config| {
+            config.pairs_mut().clear();
+            Ok(())
+        })
+    }
+    /// Returns whether the pair is already pinned, by chain and pair address.
     pub fn exist(&mut self, pair: PairConfig) -> bool {
-        self.pairs
+        self.pairs()
             .iter()
-            .find(|value| {
-                value.quote_token_symbol.to_uppercase() == pair.quote_token_symbol.to_uppercase()
-                    && value.pair_address.to_uppercase() == pair.pair_address.to_uppercase()
-                    && value.base_token_address.to_uppercase()
-                        == pair.base_token_address.to_uppercase()
-                    && value.base_token_symbol.to_uppercase()
-                        == pair.base_token_symbol.to_uppercase()
-            })
-            .is_some()
+            .any(|value| value.is_pair(&pair.chain_id, &pair.pair_address))
     }
-    pub fn delete_if_exist(&mut self, token_symbol: &str) -> Option<PairConfig> {
-        let idx = self.pairs.iter().position(|value| {
-            value.base_token_symbol.to_uppercase() == token_symbol.to_uppercase()
+    /// Removes the pinned pair of the given token on the given chain,
+    /// returning it. Only called from within [`Config::update`], which saves.
+    fn delete_if_exist(&mut self, chain_id: &str, token_address: &str) -> Option<PairConfig> {
+        let idx = self.pairs().iter().position(|value| {
+            value.chain_id.eq_ignore_ascii_case(chain_id)
+                && value.base_token_address.eq_ignore_ascii_case(token_address)
         });
-        match idx {
-            Some(idx) => Option::from(self.pairs.remove(idx)),
-            None => None,
+        idx.map(|idx| self.pairs_mut().remove(idx))
+    }
+    /// Unpins the pair with the given address on the given chain and saves
+    /// the config.
+    pub fn remove_pair(
+        &mut self,
+        chain_id: &str,
+        pair_address: &str,
+    ) -> Result<Option<PairConfig>> {
+        self.update(|config| {
+            let pairs = config.pairs_mut();
+            let idx = pairs
+                .iter()
+                .position(|value| value.is_pair(chain_id, pair_address));
+            Ok(idx.map(|idx| pairs.remove(idx)))
+        })
+    }
+
+    /// Finds the pinned pair meant by a pair address, token address, symbol
+    /// or alias, optionally prefixed by its chain as in `solana:HONEY`. Fails
+    /// naming the candidates when several pins match.
+    pub fn search_token(&self, search: &str) -> Result<Option<PairConfig>> {
+        let pairs = self.pairs();
+        let matches = pins::matching(pairs, search);
+        match matches.as_slice() {
+            [] => Ok(None),
+            [idx] => Ok(Some(pairs[*idx].clone())),
+            _ => Err(pins::ambiguous(
+                pairs,
+                search,
+                &matches,
+                "pick one by chain:symbol or pair address",
+            )),
         }
     }
-    pub fn search_token(&mut self, search: &str) -> Option<PairConfig> {
-        self.pairs
-            .iter()
-            .find(|value| {
-                value.base_token_address.to_uppercase() == search.to_uppercase()
-                    || value.pair_address.to_uppercase() == search.to_uppercase()
-                    || value.base_token_symbol.to_uppercase() == search.to_uppercase()
-                    || value.quote_token_symbol.to_uppercase() == search.to_uppercase()
-            })
-            .cloned()
+
+    /// Moves the pinned pair with the given address on the given chain to a
+    /// position, counted from 0 and clamped to the list, and saves the
+    /// config. Returns the position it ends up at.
+    pub fn move_pair(
+        &mut self,
+        chain_id: &str,
+        pair_address: &str,
+        to: usize,
+    ) -> Result<Option<usize>> {
+        self.update(|config| {
+            let pairs = config.pairs_mut();
+            let idx = pairs
+                .iter()
+                .position(|value| value.is_pair(chain_id, pair_address));
+            Ok(idx.map(|idx| {
+                let pair = pairs.remove(idx);
+                let to = to.min(pairs.len());
+                pairs.insert(to, pair);
+                to
+            }))
+        })
+    }
+
+    /// Sets or, given `None`, removes the alias of the pinned pair with the
+    /// given address on the given chain and saves the config.
+    pub fn rename_pair(
+        &mut self,
+        chain_id: &str,
+        pair_address: &str,
+        alias: Option<String>,
+    ) -> Result<Option<PairConfig>> {
+        self.update(|config| {
+            let pair = config
+                .pairs_mut()
+                .iter_mut()
+                .find(|value| value.is_pair(chain_id, pair_address));
+            Ok(pair.map(|pair| {
+                pair.alias = alias;
+                pair.clone()
+            }))
+        })
+    }
+    /// Pins a pair and saves the config, unless it is already pinned.
+    pub fn append_token(&mut self, token: PairConfig) -> Result<()> {
+        self.update(|config| {
+            if !config.exist(token.clone()) {
+                config.pairs_mut().push(token);
+            }
+            Ok(())
+        })
     }
-    pub fn append_token(&mut self, token: PairConfig) {
-        if !self.exist(token.clone()) {
-            self.pairs.push(token);
-            self.save();
+
+    /// Pins a pair in place of the one pinned for the same token on the same
+    /// chain and saves the config, returning the replaced pair.
+    pub fn replace_token(&mut self, token: PairConfig) -> Result<Option<PairConfig>> {
+        self.update(|config| {
+            let replaced = config.delete_if_exist(&token.chain_id, &token.base_token_address);
+            if !config.exist(token.clone()) {
+                config.pairs_mut().push(token);
+            }
+            Ok(replaced)
+        })
+    }
+
+    /// Returns the alert rules in the order they were added.
+    pub fn alerts(&self) -> &[AlertRule] {
+        match &self.profile {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .map(|profile| profile.alerts.as_slice())
+                .unwrap_or_default(),
+            None => &self.alerts,
         }
     }
 
-    fn save(&self) {
-        let config_path = Config::get_config_path();
-        let toml = toml::to_string(&self).expect("Cannot serialize config");
-        let mut file = File::create(&config_path).expect("Cannot create config file");
-        file.write_all(toml.as_bytes())
-            .expect("Cannot write config file");
+    /// Adds an alert rule and saves the config.
+    pub fn add_alert(&mut self, rule: AlertRule) -> Result<()> {
+        self.update(|config| {
+            config.lists_mut().1.push(rule);
+            Ok(())
+        })
+    }
+
+    /// Removes the alert rule at the given index and saves the config.
+    pub fn remove_alert(&mut self, idx: usize) -> Result<Option<AlertRule>> {
+        self.update(|config| {
+            let alerts = config.lists_mut().1;
+            if idx >= alerts.len() {
+                return Ok(None);
+            }
+            Ok(Some(alerts.remove(idx)))
+        })
     }
 
-    fn get_config_path() -> PathBuf {
-        let mut config_path = dirs::home_dir().expect("Cannot find home dir");
-        config_path.push(".config/token-tide/config.toml");
-        config_path
+    /// Returns the notifiers triggered alerts are sent to.
+    pub fn notifiers(&self) -> &[NotifierConfig] {
+        &self.notifiers
     }
 
-    fn create_default_config() -> Config {
-        let config_path = Config::get_config_path();
-        const DEFAULT_CONFIG: Config = Config { pairs: vec![] };
-        let toml = toml::to_string(&DEFAULT_CONFIG).expect("Cannot serialize default config");
-        if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent).expect("Cannot create config dir");
+    /// Returns the portfolio holdings in the order they were added.
+    pub fn holdings(&self) -> &[Holding] {
+        &self.holdings
+    }
+
+    /// Removes the holding of the given token, named like a pin as taken by
+    /// [`pins::matching`], and saves the config. A token that names several
+    /// holdings is an error listing them.
+    pub fn remove_holding(&mut self, token: &str) -> Result<Option<Holding>> {
+        self.update(|config| {
+            let pairs: Vec<PairConfig> = config
+                .holdings
+                .iter()
+                .map(|holding| holding.pair.clone())
+                .collect();
+            let matches = pins::matching(&pairs, token);
+            match matches.as_slice() {
+                [] => Ok(None),
+                [idx] => Ok(Some(config.holdings.remove(*idx))),
+                _ => Err(pins::ambiguous(
+                    &pairs,
+                    token,
+                    &matches,
+                    "pick one by chain:symbol or pair address",
+                )),
+            }
+        })
+    }
+
+    /// Returns the recorded buys and sells in the order they were added.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Records a buy or sell and saves the config. The matching holding is
+    /// adjusted by what the trade adds to or takes from the first-in-first-out
+    /// book of the pair, the model `tt portfolio pnl` uses by default. Fails
+    /// without saving if a sell would exceed the amount bought by then.
+    pub fn record_transaction(&mut self, transaction: Transaction) -> Result<()> {
+        self.update(|config| config.apply_transaction(transaction))
+    }
+
+    fn apply_transaction(&mut self, transaction: Transaction) -> Result<()> {
+        let key = transaction.pair.key();
+        let held = |transactions: &[Transaction]| -> Result<(f64, f64)> {
+            Ok(pnl::books(transactions, pnl::Method::Fifo)?
+                .iter()
+                .find(|book| book.pair.key() == key)
+                .map(|book| (book.amount(), book.cost_basis()))
+                .unwrap_or_default())
+        };
+        let (amount_before, cost_before) = held(&self.transactions)?;
+        self.transactions.push(transaction.clone());
+        let (amount_after, cost_after) = match held(&self.transactions) {
+            Ok(after) => after,
+            Err(err) => {
+                self.transactions.pop();
+                return Err(err);
+            }
+        };
+        let amount = amount_after - amount_before;
+        let cost = cost_after - cost_before;
+        match self
+            .holdings
+            .iter()
+            .position(|value| value.pair.key() == key)
+        {
+            Some(idx) => {
+                let holding = &mut self.holdings[idx];
+                holding.amount += amount;
+                holding.cost_basis = holding.cost_basis.map(|value| (value + cost).max(0.0));
+                if holding.amount <= pnl::DUST {
+                    self.holdings.remove(idx);
+                }
+            }
+            None if amount > pnl::DUST => self.holdings.push(Holding {
+                amount,
+                cost_basis: Some(cost),
+                pair: transaction.pair,
+            }),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the timeouts, retries and rate limit of API requests.
+    pub fn http(&self) -> &HttpSettings {
+        &self.http
+    }
+
+    /// Returns how long API responses are cached.
+    pub fn cache(&self) -> &CacheSettings {
+        &self.cache
+    }
+
+    /// Returns the default path of the config file: `$TOKEN_TIDE_CONFIG` if
+    /// set, else `token-tide/config.toml` under `$XDG_CONFIG_HOME` or
+    /// `~/.config`.
+    pub fn path() -> Result<PathBuf> {
+        Config::get_config_path()
+    }
+
+    /// Returns the file this config is saved to, if any.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Saves the config to the given file from now on, and writes it there
+    /// over whatever the file holds.
+    pub(crate) fn save_to(&mut self, config_path: &Path) -> Result<()> {
+        self.file = Some(config_path.to_path_buf());
+        let _lock = lock(config_path)?;
+        self.write()
+    }
+
+    /// Makes a change and saves it. With the lock held, the file is reloaded
+    /// first if another process saved it since, so that process's change is
+    /// kept and this one is made on top of it.
+    fn update<T>(&mut self, change: impl FnOnce(&mut Config) -> Result<T>) -> Result<T> {
+        let Some(config_path) = self.file.clone() else {
+            return change(self);
+        };
+        let _lock = lock(&config_path)?;
+        match fs::read_to_string(&config_path) {
+            Ok(text) if self.synced.as_ref() != Some(&text) => {
+                let profile = self.profile.take();
+                *self = Config::parse(&config_path, text)?.0;
+                self.profile = profile;
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(source) => {
+                return Err(TokenTideError::ConfigIo {
+                    path: config_path,
+                    source,
+                })
+            }
+        }
+        let changed = change(self)?;
+        self.write()?;
+        Ok(changed)
+    }
+
+    /// Writes the config to a temp file renamed over the config file, unless
+    /// the file already holds it. The caller holds the lock.
+    fn write(&mut self) -> Result<()> {
+        let Some(config_path) = self.file.clone() else {
+            return Ok(());
+        };
+        let toml = toml::to_string(self)?;
+        if self.synced.as_ref() == Some(&toml) && config_path.exists() {
+            return Ok(());
         }
-        let mut file = File::create(&config_path).expect("Cannot create config file");
-        file.write_all(toml.as_bytes())
-            .expect("Cannot write config files");
-        DEFAULT_CONFIG
+        write_atomic(&config_path, toml.as_bytes()).map_err(|source| TokenTideError::ConfigIo {
+            path: config_path.clone(),
+            source,
+        })?;
+        self.synced = Some(toml);
+        Ok(())
+    }
+
+    fn get_config_path() -> Result<PathBuf> {
+        resolve_path(|key| env::var_os(key), dirs::home_dir()).ok_or_else(|| {
+            TokenTideError::ConfigIo {
+                path: PathBuf::from("~"),
+                source: io::Error::new(io::ErrorKind::NotFound, "Cannot find home dir"),
+            }
+        })
     }
 
-    pub fn load() -> Config {
-        let config_path = Config::get_config_path();
+    /// Loads the config from its default path, see [`Config::path`].
+    pub fn load() -> Result<Config> {
+        Config::load_from(&Config::get_config_path()?)
+    }
 
+    /// Loads the config from the given file, creating an empty one if it does
+    /// not exist. A file that is not valid TOML is reported as
+    /// [`TokenTideError::ConfigParse`] and left untouched.
+    pub fn load_from(config_path: &Path) -> Result<Config> {
+        let config_path = config_path.to_path_buf();
         if !config_path.exists() {
-            return Config::create_default_config();
+            // Another process may create it first, which `update` picks up.
+            let mut config = Config {
+                file: Some(config_path),
+                ..Config::default()
+            };
+            config.update(|_| Ok(()))?;
+            return Ok(config);
         }
 
         let config_content =
-            fs::read_to_string(config_path.clone()).expect("Cannot read config file");
-        let config = toml::from_str(&config_content);
-        match config {
-            Ok(config) => config,
-            Err(err) => {
-                eprintln!("Error parsing config file: {}", err);
-                if let Err(delete_err) = fs::remove_file(&config_path.clone()) {
-                    eprintln!("Error deleting config file: {}", delete_err);
-                }
-                return Config::create_default_config();
-            }
+            fs::read_to_string(&config_path).map_err(|source| TokenTideError::ConfigIo {
+                path: config_path.clone(),
+                source,
+            })?;
+        let (mut config, version) = Config::parse(&config_path, config_content)?;
+        if version < migrate::CURRENT_VERSION {
+            doctor::backup(&config_path)?;
+            config.update(|_| Ok(()))?;
         }
+        Ok(config)
     }
+
+    /// Parses the text of a config file, upgrading an older schema. Returns
+    /// the config and the version the file was written for.
+    fn parse(config_path: &Path, text: String) -> Result<(Config, i64)> {
+        let parse_err = |source| TokenTideError::ConfigParse {
+            path: config_path.to_path_buf(),
+            source,
+        };
+        let version = toml::from_str::<Versioned>(&text)
+            .map_err(parse_err)?
+            .version
+            .unwrap_or(1);
+        let mut config: Config = match version {
+            migrate::CURRENT_VERSION => toml::from_str(&text).map_err(parse_err)?,
+            1..migrate::CURRENT_VERSION => {
+                let mut table: toml::Table = toml::from_str(&text).map_err(parse_err)?;
+                migrate::migrate(&mut table, version, unix_now());
+                toml::Value::Table(table).try_into().map_err(parse_err)?
+            }
+            _ => {
+                return Err(TokenTideError::ConfigVersion {
+                    path: config_path.to_path_buf(),
+                    version,
+                })
+            }
+        };
+        config.file = Some(config_path.to_path_buf());
+        config.synced = Some(text);
+        Ok((config, version))
+    }
+}
+
+/// A file next to the config, e.g. `config.toml.lock`.
+fn sibling(config_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = config_path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", suffix));
+    config_path.with_file_name(name)
+}
+
+/// Writes a file through a temp file next to it renamed over it, so readers
+/// see either the old or the new contents, never half of them.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let temp_path = sibling(
+        path,
+        &format!(
+            "tmp-{}-{}",
+            process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ),
+    );
+    let written = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&temp_path, path))
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
+/// Takes the advisory lock guarding changes to a file such as the config,
+/// creating its directory if needed. The lock is released when the file is dropped.
+pub(crate) fn lock(config_path: &Path) -> Result<File> {
+    let lock_path = sibling(config_path, "lock");
+    let io_err = |source| TokenTideError::ConfigIo {
+        path: lock_path.clone(),
+        source,
+    };
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent).map_err(io_err)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(io_err)?;
+    file.lock().map_err(io_err)?;
+    Ok(file)
+}
+
+/// Picks the config path from the environment, ignoring a relative
+/// `XDG_CONFIG_HOME` as the XDG spec asks.
+fn resolve_path(var: impl Fn(&str) -> Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+    let set = |key| var(key).filter(|value: &OsString| !value.is_empty());
+    if let Some(path) = set(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let config_home = set("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home.map(|home| home.join(".config")))?;
+    Some(config_home.join("token-tide/config.toml"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pnl::Side;
+    use crate::test_util::TempDir;
+
+    /// A config backed by a fresh file in a temp dir, removed on drop.
+    struct TempConfig {
+        config: Config,
+        dir: TempDir,
+    }
+
+    impl TempConfig {
+        fn new(name: &str) -> TempConfig {
+            let dir = TempDir::new(&format!("config-{}", name));
+            let config = Config::load_from(&dir.join("config.toml")).unwrap();
+            TempConfig { config, dir }
+        }
+    }
 
     #[test]
     fn test_append_clear_token() {
-        let mut config = Config::load();
-        config.clear();
+        let mut temp = TempConfig::new("append-clear");
+        let config = &mut temp.config;
+        config.clear().unwrap();
         let token = PairConfig {
             chain_id: "solana".to_string(),
             base_token_symbol: "TEST".to_string(),
             quote_token_symbol: "USDT".to_string(),
             base_token_address: "test_address".to_string(),
             pair_address: "test_pair_address".to_string(),
+            dex_id: None,
+            added_at: None,
+            alias: None,
         };
 
-        config.append_token(token);
+        config.append_token(token).unwrap();
         assert_eq!(config.pairs.len(), 1);
         assert_eq!(config.pairs[0].base_token_symbol, "TEST");
         assert_eq!(config.pairs[0].quote_token_symbol, "USDT");
         assert_eq!(config.pairs[0].base_token_address, "test_address");
         assert_eq!(config.pairs[0].pair_address, "test_pair_address");
 
-        config.clear();
+        config.clear().unwrap();
         assert_eq!(config.pairs.len(), 0);
     }
 
     #[test]
     fn test_append_tokens() {
-        let mut config = Config::load();
-        config.clear();
+        let mut temp = TempConfig::new("append");
+        let config = &mut temp.config;
+        config.clear().unwrap();
         let token = PairConfig {
             chain_id: "solana".to_string(),
             base_token_symbol: "TEST".to_string(),
             quote_token_symbol: "USDT".to_string(),
             base_token_address: "test_address".to_string(),
             pair_address: "test_pair_address".to_string(),
+            dex_id: None,
+            added_at: None,
+            alias: None,
         };
 
         let token2 = PairConfig {
@@ -171,103 +801,536 @@ mod tests {
             quote_token_symbol: "USDT".to_string(),
             base_token_address: "test_address2".to_string(),
             pair_address: "test_pair_address2".to_string(),
+            dex_id: None,
+            added_at: None,
+            alias: None,
         };
 
-        config.append_token(token);
-        config.append_token(token2);
+        config.append_token(token).unwrap();
+        config.append_token(token2).unwrap();
         assert_eq!(config.pairs.len(), 2);
         assert_eq!(config.pairs[0].base_token_symbol, "TEST");
         assert_eq!(config.pairs[1].base_token_symbol, "TEST2");
 
-        config.clear();
+        config.clear().unwrap();
         assert_eq!(config.pairs.len(), 0);
     }
 
     #[test]
     fn test_repeat_tokens() {
-        let mut config = Config::load();
-        config.clear();
+        let mut temp = TempConfig::new("repeat");
+        let config = &mut temp.config;
+        config.clear().unwrap();
         let token = PairConfig {
             chain_id: "solana".to_string(),
             base_token_symbol: "TEST".to_string(),
             quote_token_symbol: "USDT".to_string(),
             base_token_address: "test_address".to_string(),
             pair_address: "test_pair_address".to_string(),
+            dex_id: None,
+            added_at: None,
+            alias: None,
         };
 
-        config.append_token(token.clone());
-        config.append_token(token.clone());
-        config.append_token(token.clone());
+        config.append_token(token.clone()).unwrap();
+        config.append_token(token.clone()).unwrap();
+        config.append_token(token.clone()).unwrap();
         assert_eq!(config.pairs.len(), 1);
         assert_eq!(config.pairs[0].base_token_symbol, "TEST");
         assert_eq!(config.pairs[0].quote_token_symbol, "USDT");
         assert_eq!(config.pairs[0].base_token_address, "test_address");
         assert_eq!(config.pairs[0].pair_address, "test_pair_address");
 
-        config.clear();
+        config.clear().unwrap();
         assert_eq!(config.pairs.len(), 0);
     }
 
     #[test]
     fn test_search_tokens() {
-        let mut config = Config::load();
-        config.clear();
+        let mut temp = TempConfig::new("search");
+        let config = &mut temp.config;
+        config.clear().unwrap();
         let token = PairConfig {
             chain_id: "solana".to_string(),
             base_token_symbol: "HONEY".to_string(),
             quote_token_symbol: "USDT".to_string(),
             base_token_address: "4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy".to_string(),
             pair_address: "2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo".to_string(),
+            dex_id: None,
+            added_at: None,
+            alias: None,
         };
-        assert_eq!(config.search_token("HO").is_some(), false);
-        assert_eq!(config.search_token("HONEY").is_some(), false);
-        assert_eq!(config.search_token("USDT").is_some(), false);
-        assert_eq!(
-            config
-                .search_token("4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy")
-                .is_some(),
-            false
-        );
-        assert_eq!(
-            config
-                .search_token("2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo")
-                .is_some(),
-            false
-        );
-        config.append_token(token);
-        assert_eq!(config.search_token("HO").is_some(), false);
-        assert_eq!(config.search_token("HONEY").is_some(), true);
-        assert_eq!(config.search_token("honey").is_some(), true);
-        assert_eq!(config.search_token("USDT").is_some(), true);
-        assert_eq!(
-            config
-                .search_token("4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy")
-                .is_some(),
-            true
-        );
-        assert_eq!(
-            config
-                .search_token("2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo")
-                .is_some(),
-            true
-        );
+        assert!(config.search_token("HO").unwrap().is_none());
+        assert!(config.search_token("HONEY").unwrap().is_none());
+        assert!(config.search_token("USDT").unwrap().is_none());
+        assert!(config
+            .search_token("4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy")
+            .unwrap()
+            .is_none());
+        assert!(config
+            .search_token("2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo")
+            .unwrap()
+            .is_none());
+        config.append_token(token).unwrap();
+        assert!(config.search_token("HO").unwrap().is_none());
+        assert!(config.search_token("HONEY").unwrap().is_some());
+        assert!(config.search_token("honey").unwrap().is_some());
+        // The quote token does not name a pin.
+        assert!(config.search_token("USDT").unwrap().is_none());
+        assert!(config
+            .search_token("4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy")
+            .unwrap()
+            .is_some());
+        assert!(config
+            .search_token("2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo")
+            .unwrap()
+            .is_some());
     }
 
     #[test]
     fn delete_if_exist() {
-        let mut config = Config::load();
-        config.clear();
-        assert_eq!(config.delete_if_exist("HONEY").is_some(), false);
+        let mut temp = TempConfig::new("delete");
+        let config = &mut temp.config;
+        config.clear().unwrap();
+        assert!(config
+            .delete_if_exist("solana", "4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy")
+            .is_none());
         let token = PairConfig {
             chain_id: "solana".to_string(),
             base_token_symbol: "HONEY".to_string(),
             quote_token_symbol: "USDT".to_string(),
             base_token_address: "4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy".to_string(),
             pair_address: "2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo".to_string(),
+            dex_id: None,
+            added_at: None,
+            alias: None,
         };
-        config.append_token(token);
+        config.append_token(token).unwrap();
         assert_eq!(config.pairs.len(), 1);
-        assert_eq!(config.delete_if_exist("HONEY").is_some(), true);
+        assert!(config
+            .delete_if_exist("solana", "4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy")
+            .is_some());
         assert_eq!(config.pairs.len(), 0);
     }
+
+    #[test]
+    fn remove_pair() {
+        let mut temp = TempConfig::new("remove");
+        let config = &mut temp.config;
+        config.clear().unwrap();
+        assert!(config
+            .remove_pair("solana", "test_pair_address")
+            .unwrap()
+            .is_none());
+        let token = PairConfig {
+            chain_id: "solana".to_string(),
+            base_token_symbol: "TEST".to_string(),
+            quote_token_symbol: "USDT".to_string(),
+            base_token_address: "test_address".to_string(),
+            pair_address: "test_pair_address".to_string(),
+            dex_id: None,
+            added_at: None,
+            alias: None,
+        };
+        config.append_token(token).unwrap();
+        assert!(config
+            .remove_pair("Solana", "TEST_PAIR_ADDRESS")
+            .unwrap()
+            .is_some());
+        assert_eq!(config.pairs.len(), 0);
+    }
+
+    #[test]
+    fn test_load_from_saves_back() {
+        let mut temp = TempConfig::new("load-from");
+        let path = temp.dir.join("config.toml");
+        assert_eq!(temp.config.file(), Some(path.as_path()));
+        temp.config
+            .append_token(PairConfig::from(crate::pair::honey_pair()))
+            .unwrap();
+        let reloaded = Config::load_from(&path).unwrap();
+        assert_eq!(reloaded.pairs(), temp.config.pairs());
+
+        let mut in_memory = Config::default();
+        in_memory
+            .append_token(PairConfig::from(crate::pair::honey_pair()))
+            .unwrap();
+        assert_eq!(in_memory.file(), None);
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let home = Some(PathBuf::from("/home/me"));
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        assert_eq!(
+            resolve_path(env(&[]), home.clone()),
+            Some(PathBuf::from("/home/me/.config/token-tide/config.toml"))
+        );
+        assert_eq!(
+            resolve_path(env(&[("XDG_CONFIG_HOME", "/xdg")]), home.clone()),
+            Some(PathBuf::from("/xdg/token-tide/config.toml"))
+        );
+        assert_eq!(
+            resolve_path(env(&[("XDG_CONFIG_HOME", "relative")]), home.clone()),
+            Some(PathBuf::from("/home/me/.config/token-tide/config.toml"))
+        );
+        assert_eq!(
+            resolve_path(
+                env(&[("XDG_CONFIG_HOME", "/xdg"), (CONFIG_ENV, "/etc/tt.toml")]),
+                None
+            ),
+            Some(PathBuf::from("/etc/tt.toml"))
+        );
+        assert_eq!(resolve_path(env(&[(CONFIG_ENV, "")]), None), None);
+    }
+
+    fn token(idx: usize) -> PairConfig {
+        PairConfig {
+            chain_id: "solana".to_string(),
+            base_token_symbol: format!("T{}", idx),
+            quote_token_symbol: "USDC".to_string(),
+            base_token_address: format!("token_{}", idx),
+            pair_address: format!("pair_{}", idx),
+            dex_id: None,
+            added_at: None,
+            alias: None,
+        }
+    }
+
+    #[test]
+    fn test_concurrent_saves_merge() {
+        let temp = TempConfig::new("concurrent");
+        let path = temp.dir.join("config.toml");
+        // Every config is loaded before any is saved, so all but the first
+        // save see a file changed behind their back.
+        let configs: Vec<Config> = (0..8).map(|_| Config::load_from(&path).unwrap()).collect();
+        let handles: Vec<_> = configs
+            .into_iter()
+            .enumerate()
+            .map(|(idx, mut config)| {
+                std::thread::spawn(move || {
+                    config.append_token(token(idx)).unwrap();
+                    config.add_alert(AlertRule {
+                        token: format!("T{}", idx),
+                        metric: crate::alert::Metric::PriceUsd,
+                        condition: crate::alert::Condition::Above,
+                        threshold: idx as f64,
+                    })
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.pairs().len(), 8);
+        assert_eq!(config.alerts().len(), 8);
+
+        let mut names: Vec<String> = fs::read_dir(&temp.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["config.toml", "config.toml.lock"]);
+    }
+
+    #[test]
+    fn test_replace_token_after_external_save() {
+        let mut temp = TempConfig::new("replace");
+        let path = temp.dir.join("config.toml");
+        temp.config.append_token(token(1)).unwrap();
+        let mut other = Config::load_from(&path).unwrap();
+        other.append_token(token(2)).unwrap();
+
+        let replacement = PairConfig {
+            pair_address: "pair_1b".to_string(),
+            ..token(1)
+        };
+        let replaced = temp.config.replace_token(replacement).unwrap();
+        assert_eq!(replaced, Some(token(1)));
+        let addresses: Vec<&str> = temp
+            .config
+            .pairs()
+            .iter()
+            .map(|pair| pair.pair_address.as_str())
+            .collect();
+        assert_eq!(addresses, ["pair_2", "pair_1b"]);
+        assert_eq!(
+            Config::load_from(&path).unwrap().pairs(),
+            temp.config.pairs()
+        );
+    }
+
+    #[test]
+    fn test_load_migrates_in_place() {
+        let temp = TempConfig::new("migrate");
+        let path = temp.dir.join("config.toml");
+        let v1 = "[[pairs]]\nchain_id = \"Solana\"\nbase_token_symbol = \"TEST\"\n\
+                  quote_token_symbol = \"USDT\"\nbase_token_address = \"test_address\"\n\
+                  pair_address = \"test_pair_address\"\n";
+        fs::write(&path, v1).unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.pairs()[0].chain_id, "solana");
+        assert!(config.pairs()[0].added_at.is_some());
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with(&format!("version = {}", migrate::CURRENT_VERSION)));
+        let backups: Vec<String> = fs::read_dir(&temp.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().contains(".bak-"))
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        assert_eq!(backups, [v1]);
+
+        // Loading the upgraded file changes nothing.
+        Config::load_from(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), saved);
+
+        fs::write(&path, "version = 99\npairs = []\n").unwrap();
+        assert!(matches!(
+            Config::load_from(&path),
+            Err(TokenTideError::ConfigVersion { version: 99, .. })
+        ));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "version = 99\npairs = []\n"
+        );
+    }
+
+    #[test]
+    fn test_move_and_rename_pair() {
+        let mut temp = TempConfig::new("move");
+        let config = &mut temp.config;
+        for idx in 0..3 {
+            config.append_token(token(idx)).unwrap();
+        }
+        assert_eq!(config.move_pair("solana", "PAIR_2", 0).unwrap(), Some(0));
+        assert_eq!(config.move_pair("solana", "pair_1", 10).unwrap(), Some(2));
+        assert_eq!(config.move_pair("solana", "missing", 0).unwrap(), None);
+        let order: Vec<&str> = config
+            .pairs()
+            .iter()
+            .map(|pair| pair.pair_address.as_str())
+            .collect();
+        assert_eq!(order, ["pair_2", "pair_0", "pair_1"]);
+
+        let renamed = config
+            .rename_pair("solana", "pair_0", Some("first".to_string()))
+            .unwrap();
+        assert_eq!(renamed.unwrap().alias.as_deref(), Some("first"));
+        assert_eq!(
+            config.search_token("FIRST").unwrap().unwrap().pair_address,
+            "pair_0"
+        );
+        config.rename_pair("solana", "pair_0", None).unwrap();
+        assert!(config.search_token("first").unwrap().is_none());
+        let path = temp.dir.join("config.toml");
+        assert_eq!(
+            Config::load_from(&path).unwrap().pairs(),
+            temp.config.pairs()
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let mut temp = TempConfig::new("profiles");
+        let path = temp.dir.join("config.toml");
+        let config = &mut temp.config;
+        config.append_token(token(0)).unwrap();
+        assert!(matches!(
+            config.use_profile("trading"),
+            Err(TokenTideError::NotFound(_))
+        ));
+        config.add_profile("trading", Some(Format::Json)).unwrap();
+        assert!(config.add_profile("trading", None).is_err());
+        config.use_profile("trading").unwrap();
+        assert_eq!(config.profile(), Some("trading"));
+        assert!(config.pairs().is_empty());
+        assert_eq!(config.format(), Some(Format::Json));
+        config.append_token(token(1)).unwrap();
+        config
+            .add_alert(AlertRule {
+                token: "T1".to_string(),
+                metric: crate::alert::Metric::PriceUsd,
+                condition: crate::alert::Condition::Above,
+                threshold: 1.0,
+            })
+            .unwrap();
+        assert!(config.search_token("T0").unwrap().is_none());
+        assert_eq!(
+            config.search_token("T1").unwrap().unwrap().pair_address,
+            "pair_1"
+        );
+
+        // Another process's save keeps the profile in use.
+        Config::load_from(&path)
+            .unwrap()
+            .append_token(token(2))
+            .unwrap();
+        config.append_token(token(3)).unwrap();
+        assert_eq!(config.profile(), Some("trading"));
+        assert_eq!(config.pairs().len(), 2);
+
+        let mut reloaded = Config::load_from(&path).unwrap();
+        assert_eq!(reloaded.pairs().len(), 2);
+        assert!(reloaded.alerts().is_empty());
+        assert_eq!(reloaded.format(), None);
+        reloaded.use_profile("trading").unwrap();
+        assert_eq!(reloaded.pairs(), config.pairs());
+        assert_eq!(reloaded.alerts().len(), 1);
+
+        reloaded
+            .set_profile_format("default", Some(Format::Csv))
+            .unwrap();
+        reloaded.set_profile_format("trading", None).unwrap();
+        assert_eq!(reloaded.format(), Some(Format::Csv));
+        let removed = reloaded.remove_profile("trading").unwrap().unwrap();
+        assert_eq!(removed.pairs.len(), 2);
+        assert!(reloaded.profiles().is_empty());
+    }
+
+    #[test]
+    fn test_holdings_on_two_chains() {
+        let mut config = Config::default();
+        let other_chain = PairConfig {
+            chain_id: "ethereum".to_string(),
+            ..token(0)
+        };
+        for _ in 0..2 {
+            config
+                .record_transaction(Transaction {
+                    date: pnl::parse_date("2024-01-01").unwrap(),
+                    side: Side::Buy,
+                    amount: 1.0,
+                    price_usd: 1.0,
+                    pair: token(0),
+                })
+                .unwrap();
+        }
+        for side in [Side::Buy, Side::Sell] {
+            config
+                .record_transaction(Transaction {
+                    date: pnl::parse_date("2024-01-01").unwrap(),
+                    side,
+                    amount: 1.0,
+                    price_usd: 1.0,
+                    pair: other_chain.clone(),
+                })
+                .unwrap();
+        }
+        // The Ethereum pair was bought and sold in full, leaving the Solana one.
+        assert_eq!(config.holdings().len(), 1);
+        assert_eq!(config.holdings()[0].pair.chain_id, "solana");
+        assert_eq!(config.holdings()[0].amount, 2.0);
+    }
+
+    #[test]
+    fn test_remove_ambiguous_holding() {
+        let mut temp = TempConfig::new("holdings");
+        let config = &mut temp.config;
+        for chain_id in ["solana", "ethereum"] {
+            config
+                .record_transaction(Transaction {
+                    date: pnl::parse_date("2024-01-01").unwrap(),
+                    side: Side::Buy,
+                    amount: 1.0,
+                    price_usd: 1.0,
+                    pair: PairConfig {
+                        chain_id: chain_id.to_string(),
+                        ..token(0)
+                    },
+                })
+                .unwrap();
+        }
+        let symbol = token(0).base_token_symbol;
+        assert!(matches!(
+            config.remove_holding(&symbol),
+            Err(TokenTideError::Invalid(_))
+        ));
+        assert_eq!(config.holdings().len(), 2);
+        let removed = config
+            .remove_holding(&format!("ethereum:{}", symbol))
+            .unwrap()
+            .unwrap();
+        assert_eq!(removed.pair.chain_id, "ethereum");
+        assert_eq!(config.holdings().len(), 1);
+    }
+
+    #[test]
+    fn test_holding_cost_basis_is_fifo() {
+        let mut config = Config::default();
+        for (side, amount, price_usd) in [
+            (Side::Buy, 100.0, 1.0),
+            (Side::Buy, 100.0, 2.0),
+            (Side::Sell, 150.0, 3.0),
+        ] {
+            config
+                .record_transaction(Transaction {
+                    date: pnl::parse_date("2024-01-01").unwrap(),
+                    side,
+                    amount,
+                    price_usd,
+                    pair: token(0),
+                })
+                .unwrap();
+        }
+        // The sell consumed the first lot and half the second, as in `pnl`.
+        assert_eq!(config.holdings()[0].amount, 50.0);
+        assert_eq!(config.holdings()[0].cost_basis, Some(100.0));
+    }
+
+    #[test]
+    fn test_same_symbol_on_two_chains() {
+        let mut temp = TempConfig::new("chains");
+        let config = &mut temp.config;
+        let solana = PairConfig {
+            base_token_symbol: "PEPE".to_string(),
+            ..token(0)
+        };
+        let ethereum = PairConfig {
+            chain_id: "ethereum".to_string(),
+            base_token_symbol: "PEPE".to_string(),
+            ..token(1)
+        };
+        config.replace_token(solana.clone()).unwrap();
+        assert_eq!(config.replace_token(ethereum.clone()).unwrap(), None);
+        assert_eq!(config.pairs().len(), 2);
+
+        match config.search_token("pepe") {
+            Err(TokenTideError::Invalid(message)) => {
+                assert!(message.starts_with("pepe matches 2 pins (#1 PEPE/USDC on solana"));
+            }
+            other => panic!("expected ambiguity, got {:?}", other),
+        }
+        assert_eq!(
+            config.search_token("ethereum:PEPE").unwrap(),
+            Some(ethereum)
+        );
+        assert_eq!(
+            config.search_token("solana:pepe").unwrap(),
+            Some(solana.clone())
+        );
+
+        // Another pool for the Solana token replaces only that pin.
+        let pool = PairConfig {
+            pair_address: "pair_0b".to_string(),
+            ..solana.clone()
+        };
+        assert_eq!(config.replace_token(pool).unwrap(), Some(solana));
+        assert_eq!(
+            config
+                .search_token("solana:pepe")
+                .unwrap()
+                .unwrap()
+                .pair_address,
+            "pair_0b"
+        );
+    }
 }
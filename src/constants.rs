@@ -1 +1,2 @@
+/// Placeholder printed for missing values.
 pub const NONE_STR: &str = "None";
@@ -0,0 +1,642 @@
+use crate::client::TokenTide;
+use crate::config::{Config, PairConfig};
+use crate::error::{Result, TokenTideError};
+use crate::pair::Pair;
+use crate::source::PriceSource;
+use crate::{constants, number, table};
+use futures::future::{FutureExt, LocalBoxFuture};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+/// Column the dashboard is sorted by, cycled with `s`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Pinned,
+    Symbol,
+    Price,
+    Change5m,
+    Change1h,
+    Change24h,
+    Volume,
+    Liquidity,
+}
+
+impl SortKey {
+    fn next(self) -> SortKey {
+        match self {
+            SortKey::Pinned => SortKey::Symbol,
+            SortKey::Symbol => SortKey::Price,
+            SortKey::Price => SortKey::Change5m,
+            SortKey::Change5m => SortKey::Change1h,
+            SortKey::Change1h => SortKey::Change24h,
+            SortKey::Change24h => SortKey::Volume,
+            SortKey::Volume => SortKey::Liquidity,
+            SortKey::Liquidity => SortKey::Pinned,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Pinned => "pinned",
+            SortKey::Symbol => "pair",
+            SortKey::Price => "price",
+            SortKey::Change5m => "5m",
+            SortKey::Change1h => "1h",
+            SortKey::Change24h => "24h",
+            SortKey::Volume => "volume",
+            SortKey::Liquidity => "liquidity",
+        }
+    }
+}
+
+/// A pinned pair and the data last fetched for it.
+#[derive(Clone, Debug)]
+pub struct DashRow {
+    pub pinned: PairConfig,
+    pub pair: Option<Pair>,
+    pub error: Option<String>,
+    position: usize,
+}
+
+impl DashRow {
+    fn price(&self) -> Option<f64> {
+        self.pair
+            .as_ref()
+            .and_then(|pair| pair.price_usd.as_deref())
+            .and_then(|price| price.parse().ok())
+    }
+
+    fn sort_value(&self, key: SortKey) -> Option<f64> {
+        let pair = self.pair.as_ref();
+        match key {
+            SortKey::Pinned => Some(self.position as f64),
+            SortKey::Symbol => None,
+            SortKey::Price => self.price(),
+            SortKey::Change5m => pair.map(|pair| pair.price_change.m5),
+            SortKey::Change1h => pair.map(|pair| pair.price_change.h1),
+            SortKey::Change24h => pair.map(|pair| pair.price_change.h24),
+            SortKey::Volume => pair.map(|pair| pair.volume.h24),
+            SortKey::Liquidity => pair
+                .and_then(|pair| pair.liquidity.as_ref())
+                .and_then(|liquidity| liquidity.usd),
+        }
+    }
+}
+
+/// What the event loop has to do after a key press.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    None,
+    Quit,
+    Refresh,
+    Add(String),
+    Remove(PairConfig),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Mode {
+    Browse,
+    Add(String),
+    ConfirmRemove,
+}
+
+/// State of the `tt dash` dashboard, independent of the terminal.
+pub struct Dashboard {
+    rows: Vec<DashRow>,
+    sort: SortKey,
+    descending: bool,
+    selected: usize,
+    mode: Mode,
+    status: String,
+}
+
+impl Dashboard {
+    pub fn new(pinned: &[PairConfig]) -> Dashboard {
+        let mut dashboard = Dashboard {
+            rows: vec![],
+            sort: SortKey::Pinned,
+            descending: false,
+            selected: 0,
+            mode: Mode::Browse,
+            status: String::new(),
+        };
+        dashboard.sync(pinned);
+        dashboard
+    }
+
+    /// Rows in display order.
+    pub fn rows(&self) -> &[DashRow] {
+        &self.rows
+    }
+
+    pub fn selected(&self) -> Option<&DashRow> {
+        self.rows.get(self.selected)
+    }
+
+    /// Rebuilds the rows after pairs were pinned or removed, keeping the data
+    /// already fetched for pairs that are still pinned.
+    pub fn sync(&mut self, pinned: &[PairConfig]) {
+        let selected = self.selected().map(|row| row.pinned.key());
+        let mut previous = std::mem::take(&mut self.rows);
+        self.rows = pinned
+            .iter()
+            .enumerate()
+            .map(|(position, pair)| {
+                let existing = previous
+                    .iter()
+                    .position(|row| row.pinned.key() == pair.key())
+                    .map(|idx| previous.swap_remove(idx));
+                DashRow {
+                    pinned: pair.clone(),
+                    pair: existing.as_ref().and_then(|row| row.pair.clone()),
+                    error: existing.and_then(|row| row.error),
+                    position,
+                }
+            })
+            .collect();
+        self.sort_rows(selected);
+    }
+
+    /// Stores the result of fetching a pinned pair.
+    pub fn update(&mut self, pinned: &PairConfig, result: Result<Pair>) {
+        let key = pinned.key();
+        if let Some(row) = self.rows.iter_mut().find(|row| row.pinned.key() == key) {
+            match result {
+                Ok(pair) => {
+                    row.pair = Some(pair);
+                    row.error = None;
+                }
+                Err(err) => row.error = Some(err.to_string()),
+            }
+        }
+    }
+
+    pub fn set_sort(&mut self, sort: SortKey, descending: bool) {
+        self.sort = sort;
+        self.descending = descending;
+        let selected = self.selected().map(|row| row.pinned.key());
+        self.sort_rows(selected);
+    }
+
+    /// Sorts the rows, keeping the cursor on the same pair. Rows without data
+    /// for the sort column go last.
+    fn sort_rows(&mut self, selected: Option<(String, String)>) {
+        let sort = self.sort;
+        let descending = self.descending;
+        self.rows.sort_by(|a, b| {
+            let ordering = if sort == SortKey::Symbol {
+                a.pinned
+                    .base_token_symbol
+                    .to_uppercase()
+                    .cmp(&b.pinned.base_token_symbol.to_uppercase())
+            } else {
+                match (a.sort_value(sort), b.sort_value(sort)) {
+                    (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                    (Some(_), None) => return Ordering::Less,
+                    (None, Some(_)) => return Ordering::Greater,
+                    (None, None) => return Ordering::Equal,
+                }
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.selected = selected
+            .and_then(|key| self.rows.iter().position(|row| row.pinned.key() == key))
+            .unwrap_or(0)
+            .min(self.rows.len().saturating_sub(1));
+    }
+
+    /// Applies a key press and returns what the event loop has to do.
+    pub fn handle_key(&mut self, code: KeyCode) -> Action {
+        match &mut self.mode {
+            Mode::Browse => match code {
+                KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+                KeyCode::Down | KeyCode::Char('j') => {
+                    if self.selected + 1 < self.rows.len() {
+                        self.selected += 1;
+                    }
+                    Action::None
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.selected = self.selected.saturating_sub(1);
+                    Action::None
+                }
+                KeyCode::Char('s') => {
+                    self.set_sort(self.sort.next(), self.descending);
+                    Action::None
+                }
+                KeyCode::Char('o') => {
+                    self.set_sort(self.sort, !self.descending);
+                    Action::None
+                }
+                KeyCode::Char('r') => Action::Refresh,
+                KeyCode::Char('a') => {
+                    self.mode = Mode::Add(String::new());
+                    Action::None
+                }
+                KeyCode::Char('d') if self.selected().is_some() => {
+                    self.mode = Mode::ConfirmRemove;
+                    Action::None
+                }
+                _ => Action::None,
+            },
+            Mode::Add(input) => match code {
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    Action::None
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                    Action::None
+                }
+                KeyCode::Enter => {
+                    let search = input.trim().to_string();
+                    self.mode = Mode::Browse;
+                    if search.is_empty() {
+                        Action::None
+                    } else {
+                        Action::Add(search)
+                    }
+                }
+                KeyCode::Esc => {
+                    self.mode = Mode::Browse;
+                    Action::None
+                }
+                _ => Action::None,
+            },
+            Mode::ConfirmRemove => {
+                self.mode = Mode::Browse;
+                match (code, self.selected()) {
+                    (KeyCode::Char('y'), Some(row)) => Action::Remove(row.pinned.clone()),
+                    _ => Action::None,
+                }
+            }
+        }
+    }
+}
+
+/// Runs the full-screen dashboard over the pairs pinned in the config until
+/// the user quits, refreshing every `refresh`.
+pub async fn run<S: PriceSource>(
+    client: &TokenTide<S>,
+    config: &mut Config,
+    refresh: Duration,
+) -> Result<()> {
+    let mut terminal = ratatui::try_init().map_err(TokenTideError::Terminal)?;
+    let result = event_loop(&mut terminal, client, config, refresh).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop<S: PriceSource>(
+    terminal: &mut DefaultTerminal,
+    client: &TokenTide<S>,
+    config: &mut Config,
+    refresh: Duration,
+) -> Result<()> {
+    let mut dashboard = Dashboard::new(config.pairs());
+    let mut refreshed_at: Option<Instant> = None;
+    // The refresh in flight. It is driven between key presses, so a slow
+    // pair never keeps the keys, `q` included, from being handled.
+    let mut refreshing: Option<LocalBoxFuture<'_, Refreshed>> = None;
+    loop {
+        if refreshing.is_none() && refreshed_at.is_none_or(|at| at.elapsed() >= refresh) {
+            dashboard.status = "Refreshing ...".to_string();
+            refreshing = Some(refresh_rows(client, &dashboard));
+            // Counted from the start, so `r` pressed meanwhile refreshes again.
+            refreshed_at = Some(Instant::now());
+        }
+        let mut timeout = Duration::from_millis(250);
+        if let Some(pending) = refreshing.as_mut() {
+            tokio::select! {
+                (pinned, results) = pending => {
+                    for (pair, result) in pinned.iter().zip(results) {
+                        dashboard.update(pair, result);
+                    }
+                    let selected = dashboard.selected().map(|row| row.pinned.key());
+                    dashboard.sort_rows(selected);
+                    dashboard.status = String::new();
+                    refreshing = None;
+                }
+                _ = tokio::time::sleep(timeout) => {}
+            }
+            timeout = Duration::ZERO;
+        }
+        draw_terminal(terminal, &dashboard)?;
+
+        if !event::poll(timeout).map_err(TokenTideError::Terminal)? {
+            continue;
+        }
+        let Event::Key(key) = event::read().map_err(TokenTideError::Terminal)? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match dashboard.handle_key(key.code) {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::Refresh => refreshed_at = None,
+            Action::Add(search) => match client.add(config, &search).await {
+                Ok(outcome) => {
+                    dashboard.sync(config.pairs());
+                    dashboard.status = format!(
+                        "Pinned {} ({})",
+                        outcome.added.base_token_symbol, outcome.added.pair_address
+                    );
+                    refreshed_at = None;
+                }
+                Err(err) => dashboard.status = format!("Error: {}", err),
+            },
+            Action::Remove(pair) => match config.remove_pair(&pair.chain_id, &pair.pair_address) {
+                Ok(_) => {
+                    dashboard.sync(config.pairs());
+                    dashboard.status = format!("Removed {}", pair.base_token_symbol);
+                }
+                Err(err) => dashboard.status = format!("Error: {}", err),
+            },
+        }
+    }
+}
+
+/// The pairs a refresh fetched, with their results in the same order.
+type Refreshed = (Vec<PairConfig>, Vec<Result<Pair>>);
+
+/// Fetches every row concurrently, batched per chain where the source allows.
+fn refresh_rows<'a, S: PriceSource>(
+    client: &'a TokenTide<S>,
+    dashboard: &Dashboard,
+) -> LocalBoxFuture<'a, Refreshed> {
+    let pinned: Vec<PairConfig> = dashboard
+        .rows()
+        .iter()
+        .map(|row| row.pinned.clone())
+        .collect();
+    async move {
+        let jobs = pinned.len();
+        let results = client.fetch_each(&pinned, jobs).await;
+        (pinned, results)
+    }
+    .boxed_local()
+}
+
+fn draw_terminal(terminal: &mut DefaultTerminal, dashboard: &Dashboard) -> Result<()> {
+    terminal
+        .draw(|frame| draw(frame, dashboard))
+        .map(|_| ())
+        .map_err(TokenTideError::Terminal)
+}
+
+fn change_cell(change: Option<f64>) -> Cell<'static> {
+    match change {
+        Some(change) => {
+            let color = if change > 0.0 {
+                Color::Green
+            } else if change < 0.0 {
+                Color::Red
+            } else {
+                Color::Reset
+            };
+            Cell::from(format!("{:+.2}%", change)).style(Style::default().fg(color))
+        }
+        None => Cell::from(constants::NONE_STR),
+    }
+}
+
+fn draw(frame: &mut Frame, dashboard: &Dashboard) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [list, detail] =
+        Layout::horizontal([Constraint::Percentage(62), Constraint::Percentage(38)]).areas(main);
+
+    let header = [
+        ("Pair", SortKey::Symbol),
+        ("Price In USD", SortKey::Price),
+        ("5m", SortKey::Change5m),
+        ("1h", SortKey::Change1h),
+        ("24h", SortKey::Change24h),
+        ("Volume", SortKey::Volume),
+        ("Liquidity", SortKey::Liquidity),
+        ("Buys/Sells", SortKey::Pinned),
+    ]
+    .map(|(title, key)| {
+        if key == dashboard.sort && key != SortKey::Pinned {
+            let arrow = if dashboard.descending { "▼" } else { "▲" };
+            Cell::from(format!("{} {}", title, arrow))
+        } else {
+            Cell::from(title)
+        }
+    });
+    let rows = dashboard.rows().iter().map(|row| {
+        let symbol = format!(
+            "{}{}",
+            row.pinned.base_token_symbol, row.pinned.quote_token_symbol
+        );
+        match &row.pair {
+            Some(pair) => Row::new(vec![
+                Cell::from(symbol),
+                Cell::from(
+                    pair.price_usd
+                        .clone()
+                        .unwrap_or(constants::NONE_STR.to_string()),
+                ),
+                change_cell(Some(pair.price_change.m5)),
+                change_cell(Some(pair.price_change.h1)),
+                change_cell(Some(pair.price_change.h24)),
+                Cell::from(number::format_with_unit(pair.volume.h24)),
+                Cell::from(
+                    pair.liquidity
+                        .as_ref()
+                        .and_then(|liquidity| liquidity.usd)
+                        .map(|usd| format!("${}", number::format_with_unit(usd)))
+                        .unwrap_or(constants::NONE_STR.to_string()),
+                ),
+                Cell::from(format!("{}/{}", pair.txns.h24.buys, pair.txns.h24.sells)),
+            ]),
+            None => Row::new(vec![
+                Cell::from(symbol),
+                Cell::from(if row.error.is_some() {
+                    "Error"
+                } else {
+                    "Loading ..."
+                }),
+            ]),
+        }
+    });
+    let pairs_table = Table::new(
+        rows,
+        [
+            Constraint::Min(12),
+            Constraint::Length(14),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(9),
+            Constraint::Length(10),
+            Constraint::Length(11),
+        ],
+    )
+    .header(Row::new(header).style(Style::default().add_modifier(Modifier::BOLD)))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        " Watchlist (sorted by {}) ",
+        dashboard.sort.label()
+    )))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default().with_selected(Some(dashboard.selected));
+    frame.render_stateful_widget(pairs_table, list, &mut state);
+
+    let detail_block = Block::default().borders(Borders::ALL).title(" Details ");
+    match dashboard.selected() {
+        Some(DashRow {
+            pair: Some(pair), ..
+        }) => {
+            let rows = table::pair_properties(pair, false)
+                .into_iter()
+                .map(|(property, value)| Row::new(vec![Cell::from(property), Cell::from(value)]));
+            let detail_table =
+                Table::new(rows, [Constraint::Length(14), Constraint::Min(0)]).block(detail_block);
+            frame.render_widget(detail_table, detail);
+        }
+        Some(row) => {
+            let text = row.error.clone().unwrap_or("Loading ...".to_string());
+            frame.render_widget(Paragraph::new(text).block(detail_block), detail);
+        }
+        None => {
+            let text = "No pinned pairs, press a to add one.";
+            frame.render_widget(Paragraph::new(text).block(detail_block), detail);
+        }
+    }
+
+    let status_line = match &dashboard.mode {
+        Mode::Add(input) => format!("Add pair (token or pair address): {}_", input),
+        Mode::ConfirmRemove => format!(
+            "Remove {}? (y/n)",
+            dashboard
+                .selected()
+                .map(|row| row.pinned.base_token_symbol.as_str())
+                .unwrap_or_default()
+        ),
+        Mode::Browse => format!(
+            "↑↓ move  s sort  o order  a add  d remove  r refresh  q quit  {}",
+            dashboard.status
+        ),
+    };
+    frame.render_widget(Paragraph::new(status_line), status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+
+    fn pinned(symbol: &str, pair_address: &str) -> PairConfig {
+        PairConfig {
+            chain_id: "solana".to_string(),
+            base_token_symbol: symbol.to_string(),
+            quote_token_symbol: "USDC".to_string(),
+            base_token_address: format!("{}_address", symbol),
+            pair_address: pair_address.to_string(),
+            dex_id: None,
+            added_at: None,
+            alias: None,
+        }
+    }
+
+    fn priced(pair_address: &str, price: &str) -> Pair {
+        let mut pair = honey_pair();
+        pair.pair_address = pair_address.to_string();
+        pair.price_usd = Some(price.to_string());
+        pair
+    }
+
+    #[test]
+    fn test_sort_by_price_keeps_selection() {
+        let mut dashboard = Dashboard::new(&[pinned("AAA", "a"), pinned("BBB", "b")]);
+        dashboard.update(&pinned("", "a"), Ok(priced("a", "1.0")));
+        dashboard.update(&pinned("", "b"), Ok(priced("b", "2.0")));
+        dashboard.handle_key(KeyCode::Down);
+        assert_eq!(dashboard.selected().unwrap().pinned.pair_address, "b");
+
+        dashboard.set_sort(SortKey::Price, true);
+        let order: Vec<&str> = dashboard
+            .rows()
+            .iter()
+            .map(|row| row.pinned.pair_address.as_str())
+            .collect();
+        assert_eq!(order, vec!["b", "a"]);
+        assert_eq!(dashboard.selected().unwrap().pinned.pair_address, "b");
+    }
+
+    #[test]
+    fn test_rows_without_data_sort_last() {
+        let mut dashboard = Dashboard::new(&[pinned("AAA", "a"), pinned("BBB", "b")]);
+        dashboard.update(&pinned("", "b"), Ok(priced("b", "2.0")));
+        dashboard.set_sort(SortKey::Price, false);
+        assert_eq!(dashboard.rows()[0].pinned.pair_address, "b");
+        assert_eq!(dashboard.rows()[1].pinned.pair_address, "a");
+    }
+
+    #[test]
+    fn test_add_and_remove_keys() {
+        let mut dashboard = Dashboard::new(&[pinned("AAA", "a")]);
+        assert_eq!(dashboard.handle_key(KeyCode::Char('a')), Action::None);
+        for c in "bonk".chars() {
+            dashboard.handle_key(KeyCode::Char(c));
+        }
+        assert_eq!(
+            dashboard.handle_key(KeyCode::Enter),
+            Action::Add("bonk".to_string())
+        );
+
+        dashboard.handle_key(KeyCode::Char('d'));
+        assert_eq!(dashboard.handle_key(KeyCode::Char('n')), Action::None);
+        dashboard.handle_key(KeyCode::Char('d'));
+        assert_eq!(
+            dashboard.handle_key(KeyCode::Char('y')),
+            Action::Remove(pinned("AAA", "a"))
+        );
+        assert_eq!(dashboard.handle_key(KeyCode::Char('q')), Action::Quit);
+    }
+
+    #[test]
+    fn test_same_address_on_two_chains() {
+        let other_chain = PairConfig {
+            chain_id: "ethereum".to_string(),
+            ..pinned("BBB", "a")
+        };
+        let mut dashboard = Dashboard::new(&[pinned("AAA", "a"), other_chain.clone()]);
+        dashboard.update(&other_chain, Ok(priced("a", "2.0")));
+        assert!(dashboard.rows()[0].pair.is_none());
+        assert!(dashboard.rows()[1].pair.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rows() {
+        let client = TokenTide::with_source(crate::source::MockSource {
+            pairs: vec![priced("a", "1.0")],
+        });
+        let dashboard = Dashboard::new(&[pinned("AAA", "a"), pinned("BBB", "b")]);
+        let (pinned, results) = refresh_rows(&client, &dashboard).await;
+        assert_eq!(pinned.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(TokenTideError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_sync_keeps_fetched_data() {
+        let mut dashboard = Dashboard::new(&[pinned("AAA", "a")]);
+        dashboard.update(&pinned("", "a"), Ok(priced("a", "1.0")));
+        dashboard.sync(&[pinned("AAA", "a"), pinned("BBB", "b")]);
+        assert_eq!(dashboard.rows().len(), 2);
+        assert!(dashboard.rows()[0].pair.is_some());
+        assert!(dashboard.rows()[1].pair.is_none());
+    }
+}
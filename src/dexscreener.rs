@@ -1,268 +1,277 @@
-use crate::config::PairConfig;
-use crate::{config, constants, number};
-use prettytable::{row, Cell, Row, Table};
-use serde::{Deserialize, Serialize};
-use std::error::Error;
+use crate::cache::{Cache, CacheMode};
+use crate::error::{Result, TokenTideError};
+use crate::http::HttpClient;
+use crate::pair::{Pair, Pairs};
+use crate::source::PriceSource;
+use async_trait::async_trait;
+use futures::future;
+use std::collections::HashSet;
+use std::sync::Mutex;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct Pair {
-    pub chain_id: String,
-    pub dex_id: String,
-    pub url: String,
-    pub pair_address: String,
-    pub base_token: Token,
-    pub quote_token: QuoteToken,
-    pub price_native: String,
-    pub price_usd: Option<String>,
-    pub txns: Transactions,
-    pub volume: Volume,
-    pub price_change: PriceChange,
-    pub liquidity: Option<Liquidity>,
-    pub fdv: Option<f64>,
-    pub pair_created_at: Option<u64>,
-}
+const DEXSCREENER_HOST: &str = "https://api.dexscreener.io";
+/// Environment variable replacing the API base URL, taking precedence over
+/// `http.base_url` in the config.
+pub const HOST_ENV: &str = "TOKEN_TIDE_API_URL";
+/// Most pair addresses the pairs endpoint accepts in one request.
+const MAX_PAIRS_PER_REQUEST: usize = 30;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Token {
-    pub address: String,
-    pub name: String,
-    pub symbol: String,
+/// [`PriceSource`] backed by the public DexScreener API.
+pub struct DexScreener {
+    host: String,
+    http: HttpClient,
+    cache: Option<Cache>,
+    /// Pairs this source fetched from the network, by chain and address.
+    /// Cache hits for other pairs hold data an earlier run already saw.
+    fetched: Mutex<HashSet<(String, String)>>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct QuoteToken {
-    pub symbol: String,
-}
+impl DexScreener {
+    /// Creates a client for the public DexScreener API.
+    pub fn new() -> DexScreener {
+        DexScreener::with_http(HttpClient::default())
+    }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Transactions {
-    pub m5: BuySell,
-    pub h1: BuySell,
-    pub h6: BuySell,
-    pub h24: BuySell,
-}
+    /// Creates a client sending its requests through the given HTTP client.
+    pub fn with_http(http: HttpClient) -> DexScreener {
+        DexScreener {
+            host: DEXSCREENER_HOST.to_string(),
+            http,
+            cache: None,
+            fetched: Mutex::new(HashSet::new()),
+        }
+    }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct BuySell {
-    pub buys: i32,
-    pub sells: i32,
-}
+    /// Sends requests to another API base URL, e.g. a mirror or a stand-in.
+    pub fn with_host(mut self, host: &str) -> DexScreener {
+        self.host = host.trim_end_matches('/').to_string();
+        self
+    }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Volume {
-    pub m5: f64,
-    pub h1: f64,
-    pub h6: f64,
-    pub h24: f64,
-}
+    pub fn host(&self) -> &str {
+        &self.host
+    }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct PriceChange {
-    pub m5: f64,
-    pub h1: f64,
-    pub h6: f64,
-    pub h24: f64,
-}
+    pub fn http(&self) -> &HttpClient {
+        &self.http
+    }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Liquidity {
-    pub usd: Option<f64>,
-    pub base: f64,
-    pub quote: f64,
-}
+    /// Serves and stores responses through the given cache.
+    pub fn with_cache(mut self, cache: Cache) -> DexScreener {
+        self.cache = Some(cache);
+        self
+    }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Pairs {
-    pub pairs: Vec<Pair>,
-}
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
 
-const DEXSCREENER_HOST: &str = "https://api.dexscreener.io";
-async fn search_token(token: &str) -> Result<Vec<Pair>, Box<dyn Error>> {
-    let url = format!("{}/latest/dex/search/?q={}", DEXSCREENER_HOST, token);
-    let response = reqwest::get(&url).await?;
-    if response.status().is_success() {
-        let text = response.text().await?;
+    fn pair_url(&self, chain_id: &str, pair_address: &str) -> String {
+        format!(
+            "{}/latest/dex/pairs/{}/{}",
+            self.host, chain_id, pair_address
+        )
+    }
+
+    /// The search URL, with the query form-encoded so `&`, `#` and `?` stay
+    /// part of it.
+    fn search_url(&self, query: &str) -> Result<String> {
+        let base = format!("{}/latest/dex/search/", self.host);
+        reqwest::Url::parse_with_params(&base, [("q", query)])
+            .map(String::from)
+            .map_err(|err| TokenTideError::Invalid(format!("{}: {}", base, err)))
+    }
+
+    fn is_offline(&self) -> bool {
+        self.cache
+            .as_ref()
+            .is_some_and(|cache| cache.mode() == CacheMode::Offline)
+    }
+
+    async fn get_pairs(&self, url: &str) -> Result<Vec<Pair>> {
+        if let Some(cache) = &self.cache {
+            if let Some(text) = cache.lookup(url) {
+                let pairs_result: Pairs = serde_json::from_str(&text)?;
+                return Ok(pairs_result.pairs);
+            }
+            if cache.mode() == CacheMode::Offline {
+                return Err(TokenTideError::NotFound(format!(
+                    "cached response for {}",
+                    url
+                )));
+            }
+        }
+        let text = self.http.get(url).await?;
         let pairs_result: Pairs = serde_json::from_str(&text)?;
+        self.fetched
+            .lock()
+            .expect("fetched lock")
+            .extend(pairs_result.pairs.iter().map(pair_key));
+        if let Some(cache) = &self.cache {
+            cache.store(url, &text);
+            // Every pair is also cached on its own, so an offline lookup of a
+            // pinned pair finds it whichever request fetched it.
+            for pair in &pairs_result.pairs {
+                let body = serde_json::to_string(&Pairs {
+                    pairs: vec![pair.clone()],
+                })?;
+                cache.store(&self.pair_url(&pair.chain_id, &pair.pair_address), &body);
+            }
+        }
         Ok(pairs_result.pairs)
-    } else {
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to fetch data: {}", response.status()),
-        )))
     }
 }
 
-async fn query_pair(chain_id: &str, pair_address: &str) -> Result<Vec<Pair>, Box<dyn Error>> {
-    let url = format!(
-        "{}/latest/dex/pairs/{}/{}",
-        DEXSCREENER_HOST, chain_id, pair_address
-    );
-    let response = reqwest::get(&url).await?;
+fn pair_key(pair: &Pair) -> (String, String) {
+    (
+        pair.chain_id.to_uppercase(),
+        pair.pair_address.to_uppercase(),
+    )
+}
 
-    if response.status().is_success() {
-        let text = response.text().await?;
-        let pairs_result: Pairs = serde_json::from_str(&text)?;
-        Ok(pairs_result.pairs)
-    } else {
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to fetch data: {}", response.status()),
-        )))
+/// Pairs endpoint URLs covering the given addresses, as few as the endpoint
+/// allows.
+fn pairs_urls(host: &str, chain_id: &str, pair_addresses: &[String]) -> Vec<String> {
+    pair_addresses
+        .chunks(MAX_PAIRS_PER_REQUEST)
+        .map(|chunk| format!("{}/latest/dex/pairs/{}/{}", host, chain_id, chunk.join(",")))
+        .collect()
+}
+
+impl Default for DexScreener {
+    fn default() -> Self {
+        DexScreener::new()
     }
 }
-pub async fn query(search: &str, simple: &bool) {
-    let search_pair: Option<PairConfig>;
-    let mut config = config::Config::load();
 
-    if let Some(token) = config.search_token(search) {
-        search_pair = Option::from(token);
-    } else {
-        let pair_result = search_token(search).await;
-        search_pair = match pair_result {
-            Ok(pairs) => pairs.get(0).cloned().map(|value| PairConfig::from(value)),
-            Err(e) => {
-                println!("Error: {}", e);
-                None
-            }
-        };
+/// The API base URL to use: [`HOST_ENV`] if set, else the configured one,
+/// else the public API.
+pub fn resolve_host(configured: Option<&str>) -> String {
+    std::env::var(HOST_ENV)
+        .ok()
+        .filter(|host| !host.trim().is_empty())
+        .or_else(|| configured.map(str::to_string))
+        .unwrap_or_else(|| DEXSCREENER_HOST.to_string())
+}
+
+#[async_trait]
+impl PriceSource for DexScreener {
+    async fn search(&self, query: &str) -> Result<Vec<Pair>> {
+        self.get_pairs(&self.search_url(query)?).await
     }
 
-    if let Some(pair) = search_pair {
-        let query_result = query_pair(pair.chain_id.as_str(), pair.pair_address.as_str()).await;
-        match query_result {
-            Ok(pairs) => {
-                let mut table = Table::new();
-                table.add_row(row!["Property", "Value"]);
-                if let Some(first_pair) = pairs.get(0) {
-                    table.add_row(row![
-                        "Pair",
-                        format!(
-                            "{}{}",
-                            first_pair.base_token.symbol, first_pair.quote_token.symbol
-                        )
-                    ]);
-                    table.add_row(row![
-                        "Price In USD",
-                        &first_pair
-                            .price_usd
-                            .clone()
-                            .unwrap_or(constants::NONE_STR.to_string())
-                    ]);
-                    table.add_row(row!["Token Address", &first_pair.base_token.address]);
+    async fn fetch_pair(&self, chain_id: &str, pair_address: &str) -> Result<Vec<Pair>> {
+        self.get_pairs(&self.pair_url(chain_id, pair_address)).await
+    }
+
+    async fn fetch_token_pairs(&self, token_address: &str) -> Result<Vec<Pair>> {
+        let url = format!("{}/latest/dex/tokens/{}", self.host, token_address);
+        self.get_pairs(&url).await
+    }
 
-                    if !simple {
-                        table.add_row(row!["Chain", &first_pair.chain_id]);
-                        table.add_row(row!["DEX", &first_pair.dex_id]);
-                        table.add_row(row![
-                            "24h Volume",
-                            &format!(
-                                "{} ({})",
-                                number::format_with_unit(first_pair.volume.h24),
-                                number::to_locale_string(first_pair.volume.h24)
-                            )
-                        ]);
-                        table.add_row(row![
-                            "FDV",
-                            &first_pair
-                                .fdv
-                                .map(|value| format!(
-                                    "{} ({})",
-                                    number::format_with_unit(value),
-                                    number::to_locale_string(value)
-                                ))
-                                .unwrap_or(constants::NONE_STR.to_string())
-                        ]);
-                        table.add_row(row![
-                            "Liquidity",
-                            &first_pair
-                                .liquidity
-                                .clone()
-                                .map(|value| format!(
-                                    "${} (${})",
-                                    number::format_with_unit(value.usd.unwrap_or(0.0)),
-                                    number::to_locale_string(value.usd.unwrap_or(0.0))
-                                ))
-                                .unwrap_or(constants::NONE_STR.to_string())
-                        ]);
-                        table.add_row(row!["Pair Address", &first_pair.pair_address]);
-                        table.add_row(row!["Link", &first_pair.url]);
-                    }
+    fn served_from_cache(&self, pair: &Pair) -> bool {
+        !self
+            .fetched
+            .lock()
+            .expect("fetched lock")
+            .contains(&pair_key(pair))
+    }
 
-                    config.append_token(PairConfig::from(first_pair.clone()));
-                } else {
-                    println!("No pairs found.");
+    async fn fetch_pairs(&self, chain_id: &str, pair_addresses: &[String]) -> Result<Vec<Pair>> {
+        if self.is_offline() {
+            // Batches are cached per pair too, so look the pairs up one by one.
+            let mut pairs = vec![];
+            let mut missing = vec![];
+            for pair_address in pair_addresses {
+                match self.fetch_pair(chain_id, pair_address).await {
+                    Ok(found) => pairs.extend(found),
+                    Err(TokenTideError::NotFound(_)) => missing.push(pair_address.as_str()),
+                    Err(err) => return Err(err),
                 }
-                table.printstd();
             }
-            Err(e) => println!("Error: {}", e),
+            if !missing.is_empty() {
+                return Err(TokenTideError::NotFound(format!(
+                    "cached response for {} on {}",
+                    missing.join(", "),
+                    chain_id
+                )));
+            }
+            return Ok(pairs);
         }
+        let urls = pairs_urls(&self.host, chain_id, pair_addresses);
+        let batches = future::try_join_all(urls.iter().map(|url| self.get_pairs(url))).await?;
+        Ok(batches.into_iter().flatten().collect())
     }
 }
 
-pub async fn search(token: &str) {
-    match search_token(token).await {
-        Ok(pairs) => {
-            let mut table = Table::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+    use crate::test_util::TempDir;
+    use std::time::Duration;
 
-            table.add_row(row![
-                "Pair",
-                "Chain",
-                "DEX",
-                "Price In USD",
-                "Token Address",
-                "Pair Address",
-            ]);
-            for pair in pairs {
-                table.add_row(Row::new(vec![
-                    Cell::new(&(pair.base_token.symbol + pair.quote_token.symbol.as_str())),
-                    Cell::new(&pair.chain_id),
-                    Cell::new(&pair.dex_id),
-                    Cell::new(&pair.price_usd.unwrap_or(constants::NONE_STR.to_string())),
-                    Cell::new(&pair.base_token.address),
-                    Cell::new(&pair.pair_address),
-                ]));
-            }
+    #[test]
+    fn test_pairs_urls() {
+        let addresses: Vec<String> = (0..31).map(|idx| format!("p{}", idx)).collect();
+        let urls = pairs_urls("https://host", "solana", &addresses);
+        assert_eq!(urls.len(), 2);
+        assert!(urls[0].starts_with("https://host/latest/dex/pairs/solana/p0,p1,"));
+        assert!(urls[0].ends_with(",p29"));
+        assert_eq!(urls[1], "https://host/latest/dex/pairs/solana/p30");
+    }
 
-            table.printstd();
-        }
-        Err(e) => println!("Error: {}", e),
+    #[test]
+    fn test_search_url() {
+        let source = DexScreener::new().with_host("http://127.0.0.1:1");
+        assert_eq!(
+            source.search_url("honey").unwrap(),
+            "http://127.0.0.1:1/latest/dex/search/?q=honey"
+        );
+        assert_eq!(
+            source.search_url("a&b #1?").unwrap(),
+            "http://127.0.0.1:1/latest/dex/search/?q=a%26b+%231%3F"
+        );
     }
-}
 
-pub async fn add(search: &str) {
-    let mut config = config::Config::load();
-    let pair_result = search_token(search).await;
-    let search_pair = match pair_result {
-        Ok(pairs) => pairs.get(0).cloned().map(|value| PairConfig::from(value)),
-        Err(e) => {
-            println!("Error: {}", e);
-            None
-        }
-    };
-    if let Some(pair) = search_pair {
-        let before_pair: Option<PairConfig> = config.delete_if_exist(&pair.base_token_symbol);
-        if let Some(before) = before_pair {
-            println!(
-                "Deleted {}. Before pair: {}",
-                before.base_token_symbol, before.pair_address
-            );
-        } else {
-            println!("No pair found before.");
-        }
+    #[tokio::test]
+    async fn test_offline() {
+        let dir = TempDir::new("offline");
+        let pair = honey_pair();
+        let seed = Cache::new(
+            dir.path().to_path_buf(),
+            Duration::from_secs(30),
+            CacheMode::Normal,
+        );
+        let source = DexScreener::new();
+        let body = serde_json::to_string(&Pairs {
+            pairs: vec![pair.clone()],
+        })
+        .unwrap();
+        seed.store(&source.pair_url(&pair.chain_id, &pair.pair_address), &body);
 
-        let query_result = query_pair(pair.chain_id.as_str(), pair.pair_address.as_str()).await;
-        match query_result {
-            Ok(pairs) => {
-                if let Some(first_pair) = pairs.get(0) {
-                    config.append_token(PairConfig::from(first_pair.clone()));
-                    println!(
-                        "Appended {}. Now pair: {}",
-                        pair.base_token_symbol, pair.pair_address
-                    );
-                }
+        let offline = DexScreener::new().with_cache(Cache::new(
+            dir.path().to_path_buf(),
+            Duration::ZERO,
+            CacheMode::Offline,
+        ));
+        let addresses = vec![pair.pair_address.clone(), "missing".to_string()];
+        match offline.fetch_pairs(&pair.chain_id, &addresses).await {
+            Err(TokenTideError::NotFound(message)) => {
+                assert_eq!(message, "cached response for missing on solana")
             }
-            Err(e) => println!("Error: {}", e),
+            other => panic!("expected the missing pair reported, got {other:?}"),
         }
+        let pairs = offline
+            .fetch_pairs(&pair.chain_id, &addresses[..1])
+            .await
+            .unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].base_token.symbol, "HONEY");
+        assert!(offline.cache().unwrap().oldest_served().is_some());
+        assert!(offline.served_from_cache(&pairs[0]));
+        assert!(matches!(
+            offline.search("honey").await,
+            Err(TokenTideError::NotFound(_))
+        ));
     }
 }
@@ -0,0 +1,654 @@
+use crate::alert::AlertRule;
+use crate::cache::CacheSettings;
+use crate::config::{Config, PairConfig};
+use crate::error::{Result, TokenTideError};
+use crate::history::unix_now;
+use crate::http::HttpSettings;
+use crate::migrate;
+use crate::notify::NotifierConfig;
+use crate::output::Format;
+use crate::pnl::{self, Transaction};
+use crate::portfolio::Holding;
+use crate::profile::Profile;
+use serde::de::{DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml::{Spanned, Table, Value};
+
+/// Something wrong with the config file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    /// Line and column of the offending text, both starting at 1, if known.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "line {}, column {}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// The outcome of checking a config file.
+#[derive(Debug)]
+pub struct Diagnosis {
+    pub problems: Vec<Problem>,
+    /// Everything that passed the checks, which is what a repair writes back.
+    pub repaired: Config,
+    /// The version of a file written by a newer `tt`, which is not repaired.
+    pub newer_version: Option<i64>,
+}
+
+/// A top-level value of the config, keeping where each entry of a list is.
+enum Section {
+    List(Vec<Spanned<Value>>),
+    Other(Value),
+}
+
+impl<'de> Deserialize<'de> for Section {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct SectionVisitor;
+
+        impl<'de> Visitor<'de> for SectionVisitor {
+            type Value = Section;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a TOML value")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<Section, A::Error> {
+                let mut entries = vec![];
+                while let Some(entry) = seq.next_element()? {
+                    entries.push(entry);
+                }
+                Ok(Section::List(entries))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Section, A::Error> {
+                // Keys of nested tables come owned, which `Value` does not take.
+                let mut table = Table::new();
+                while let Some((key, value)) = map.next_entry::<String, Value>()? {
+                    table.insert(key, value);
+                }
+                Ok(Section::Other(Value::Table(table)))
+            }
+
+            fn visit_bool<E>(self, value: bool) -> std::result::Result<Section, E> {
+                Ok(Section::Other(Value::Boolean(value)))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> std::result::Result<Section, E> {
+                Ok(Section::Other(Value::Integer(value)))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> std::result::Result<Section, E> {
+                Ok(Section::Other(Value::Float(value)))
+            }
+
+            fn visit_str<E>(self, value: &str) -> std::result::Result<Section, E> {
+                Ok(Section::Other(Value::String(value.to_string())))
+            }
+        }
+
+        deserializer.deserialize_any(SectionVisitor)
+    }
+}
+
+/// Line and column of a byte offset, both starting at 1.
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// Blanks lines that are not valid TOML until the rest parses, keeping line
+/// numbers intact. An error on a blank line, e.g. at the end of a file with
+/// an unterminated array or string, drops the last line that is not blank
+/// and everything after it, so every round removes something.
+fn strip_syntax_errors(text: &str, problems: &mut Vec<Problem>) -> String {
+    let mut text = text.to_string();
+    // The error left after dropping a line at the end, already reported.
+    let mut dropped_for: Option<String> = None;
+    loop {
+        let err = match text.parse::<Table>() {
+            Ok(_) => return text,
+            Err(err) => err,
+        };
+        let message = err.message().trim().replace('\n', ", ");
+        let Some(span) = err.span() else {
+            problems.push(Problem {
+                position: None,
+                message,
+            });
+            return String::new();
+        };
+        let offset = span.start.min(text.len());
+        if dropped_for.as_ref() != Some(&message) {
+            problems.push(Problem {
+                position: Some(position(&text, offset)),
+                message: message.clone(),
+            });
+        }
+        dropped_for = None;
+        let start = text[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        let end = text[offset..]
+            .find('\n')
+            .map_or(text.len(), |idx| offset + idx);
+        if text[start..end].trim().is_empty() {
+            let before = text[..start].trim_end();
+            if before.is_empty() {
+                return String::new();
+            }
+            let cut = before.rfind('\n').map_or(0, |idx| idx + 1);
+            text.truncate(cut);
+            dropped_for = Some(message);
+        } else {
+            text.replace_range(start..end, "");
+        }
+    }
+}
+
+/// Keeps the entries of a list that deserialize as `T` and pass `check`.
+fn entries<T: DeserializeOwned>(
+    text: &str,
+    name: &str,
+    entries: Vec<Spanned<Value>>,
+    problems: &mut Vec<Problem>,
+    mut check: impl FnMut(&T) -> std::result::Result<(), String>,
+) -> Vec<Value> {
+    let mut kept = vec![];
+    for (idx, entry) in entries.into_iter().enumerate() {
+        let at = Some(position(text, entry.span().start));
+        let value = entry.into_inner();
+        let result = value
+            .clone()
+            .try_into::<T>()
+            .map_err(|err| err.message().trim().to_string())
+            .and_then(|parsed| check(&parsed));
+        match result {
+            Ok(()) => kept.push(value),
+            Err(message) => problems.push(Problem {
+                position: at,
+                message: format!("{} entry {} dropped: {}", name, idx + 1, message),
+            }),
+        }
+    }
+    kept
+}
+
+/// Checks the text of a config file, collecting every problem instead of
+/// stopping at the first like [`Config::load`] does.
+pub fn diagnose(text: &str) -> Diagnosis {
+    let mut problems = vec![];
+    let text = strip_syntax_errors(text, &mut problems);
+    // Only keys are spanned: toml cannot span a table made by a dotted
+    // header such as `[profiles.trading]`.
+    let mut sections: Vec<(Spanned<String>, Section)> =
+        toml::from_str::<BTreeMap<Spanned<String>, Section>>(&text)
+            .map(|sections| sections.into_iter().collect())
+            .unwrap_or_default();
+    sections.sort_by_key(|(key, _)| key.span().start);
+
+    let mut table = Table::new();
+    table.insert("pairs".to_string(), Value::Array(vec![]));
+    if !sections.iter().any(|(key, _)| key.get_ref() == "pairs") {
+        problems.push(Problem {
+            position: None,
+            message: "missing `pairs`, an empty list is written".to_string(),
+        });
+    }
+    let mut version = 1;
+    let mut newer_version = None;
+    let mut pinned = vec![];
+    let mut transactions: Vec<Transaction> = vec![];
+    for (key, section) in sections {
+        let at = Some(position(&text, key.span().start));
+        let name = key.into_inner();
+        let kept = match (name.as_str(), section) {
+            ("version", Section::Other(Value::Integer(found)))
+                if (1..=migrate::CURRENT_VERSION).contains(&found) =>
+            {
+                version = found;
+                continue;
+            }
+            ("version", Section::Other(Value::Integer(found)))
+                if found > migrate::CURRENT_VERSION =>
+            {
+                newer_version = Some(found);
+                problems.push(Problem {
+                    position: at,
+                    message: format!(
+                        "version {} was written by a newer tt, which knows versions 1 to {}; \
+                         the file is not repaired",
+                        found,
+                        migrate::CURRENT_VERSION
+                    ),
+                });
+                continue;
+            }
+            ("version", _) => {
+                problems.push(Problem {
+                    position: at,
+                    message: format!(
+                        "`version` is not one of 1 to {}, the file is read as version 1",
+                        migrate::CURRENT_VERSION
+                    ),
+                });
+                continue;
+            }
+            ("pairs", Section::List(list)) => Value::Array(entries(
+                &text,
+                &name,
+                list,
+                &mut problems,
+                |pair: &PairConfig| {
+                    // The same address can be a pair on several chains.
+                    if pinned.contains(&pair.key()) {
+                        return Err(format!("pair {} is pinned twice", pair.pair_address));
+                    }
+                    pinned.push(pair.key());
+                    Ok(())
+                },
+            )),
+            ("alerts", Section::List(list)) => Value::Array(entries(
+                &text,
+                &name,
+                list,
+                &mut problems,
+                |_: &AlertRule| Ok(()),
+            )),
+            ("notifiers", Section::List(list)) => Value::Array(entries(
+                &text,
+                &name,
+                list,
+                &mut problems,
+                |_: &NotifierConfig| Ok(()),
+            )),
+            ("holdings", Section::List(list)) => Value::Array(entries(
+                &text,
+                &name,
+                list,
+                &mut problems,
+                |holding: &Holding| {
+                    if holding.amount.is_finite() && holding.amount > 0.0 {
+                        Ok(())
+                    } else {
+                        Err(format!("amount {} is not positive", holding.amount))
+                    }
+                },
+            )),
+            ("transactions", Section::List(list)) => Value::Array(entries(
+                &text,
+                &name,
+                list,
+                &mut problems,
+                |transaction: &Transaction| {
+                    transactions.push(transaction.clone());
+                    pnl::books(&transactions, pnl::Method::Fifo)
+                        .map(drop)
+                        .map_err(|err| {
+                            transactions.pop();
+                            err.to_string()
+                        })
+                },
+            )),
+            ("http", Section::Other(value)) => match value.clone().try_into::<HttpSettings>() {
+                Ok(_) => value,
+                Err(err) => {
+                    problems.push(Problem {
+                        position: at,
+                        message: format!("[http] reset to defaults: {}", err.message().trim()),
+                    });
+                    continue;
+                }
+            },
+            ("cache", Section::Other(value)) => match value.clone().try_into::<CacheSettings>() {
+                Ok(_) => value,
+                Err(err) => {
+                    problems.push(Problem {
+                        position: at,
+                        message: format!("[cache] reset to defaults: {}", err.message().trim()),
+                    });
+                    continue;
+                }
+            },
+            ("format", Section::Other(value)) => match value.clone().try_into::<Format>() {
+                Ok(_) => value,
+                Err(_) => {
+                    problems.push(Problem {
+                        position: at,
+                        message: format!(
+                            "`format` is not one of {} and is dropped",
+                            Format::NAMES.join(", ")
+                        ),
+                    });
+                    continue;
+                }
+            },
+            ("profiles", Section::Other(Value::Table(profiles))) => {
+                let mut kept = Table::new();
+                for (profile, mut value) in profiles {
+                    // Profiles are mostly written as `[profiles.<name>]`.
+                    let header = text
+                        .find(&format!("profiles.{}", profile))
+                        .map(|offset| position(&text, offset))
+                        .or(at);
+                    match value.clone().try_into::<Profile>() {
+                        Ok(parsed) => {
+                            let mut seen = vec![];
+                            let mut twice = vec![];
+                            for (idx, pair) in parsed.pairs.iter().enumerate() {
+                                if seen.contains(&pair.key()) {
+                                    problems.push(Problem {
+                                        position: header,
+                                        message: format!(
+                                            "profile `{}` pairs entry {} dropped: \
+                                             pair {} is pinned twice",
+                                            profile,
+                                            idx + 1,
+                                            pair.pair_address
+                                        ),
+                                    });
+                                    twice.push(idx);
+                                } else {
+                                    seen.push(pair.key());
+                                }
+                            }
+                            if let Some(Value::Array(pairs)) = value.get_mut("pairs") {
+                                for idx in twice.into_iter().rev() {
+                                    pairs.remove(idx);
+                                }
+                            }
+                            kept.insert(profile, value);
+                        }
+                        Err(err) => problems.push(Problem {
+                            position: header,
+                            message: format!(
+                                "profile `{}` dropped: {}",
+                                profile,
+                                err.message().trim()
+                            ),
+                        }),
+                    }
+                }
+                Value::Table(kept)
+            }
+            ("profiles", _) => {
+                problems.push(Problem {
+                    position: at,
+                    message: "`profiles` is not a table and is dropped".to_string(),
+                });
+                continue;
+            }
+            ("pairs" | "alerts" | "notifiers" | "holdings" | "transactions", _) => {
+                problems.push(Problem {
+                    position: at,
+                    message: format!("`{}` is not a list and is dropped", name),
+                });
+                continue;
+            }
+            ("http" | "cache", _) => {
+                problems.push(Problem {
+                    position: at,
+                    message: format!("`{}` is not a table and is reset to defaults", name),
+                });
+                continue;
+            }
+            _ => {
+                problems.push(Problem {
+                    position: at,
+                    message: format!("unknown key `{}` is dropped", name),
+                });
+                continue;
+            }
+        };
+        table.insert(name, kept);
+    }
+    migrate::migrate(&mut table, version, unix_now());
+    let repaired = Value::Table(table)
+        .try_into()
+        .expect("every kept entry deserialized on its own");
+    Diagnosis {
+        problems,
+        repaired,
+        newer_version,
+    }
+}
+
+/// Reads and checks a config file. A missing file has no problems.
+pub fn check(config_path: &Path) -> Result<Diagnosis> {
+    match fs::read_to_string(config_path) {
+        Ok(text) => Ok(diagnose(&text)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(diagnose("pairs = []")),
+        Err(source) => Err(TokenTideError::ConfigIo {
+            path: config_path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Copies the config file to `config.toml.bak-<timestamp>` beside it.
+pub fn backup(config_path: &Path) -> Result<PathBuf> {
+    let mut name = config_path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".bak-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let backup_path = config_path.with_file_name(name);
+    fs::copy(config_path, &backup_path).map_err(|source| TokenTideError::ConfigIo {
+        path: backup_path.clone(),
+        source,
+    })?;
+    Ok(backup_path)
+}
+
+/// Checks a config file and, if it has problems, backs it up and replaces it
+/// with the repaired config. Returns the diagnosis and the backup, if made.
+/// A file written by a newer `tt` is refused like [`Config::load`] does,
+/// rather than rewritten for this version.
+pub fn repair(config_path: &Path) -> Result<(Diagnosis, Option<PathBuf>)> {
+    let mut diagnosis = check(config_path)?;
+    if let Some(version) = diagnosis.newer_version {
+        return Err(TokenTideError::ConfigVersion {
+            path: config_path.to_path_buf(),
+            version,
+        });
+    }
+    if diagnosis.problems.is_empty() {
+        return Ok((diagnosis, None));
+    }
+    let backup_path = backup(config_path)?;
+    diagnosis.repaired.save_to(config_path)?;
+    Ok((diagnosis, Some(backup_path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const HONEY: &str = r#"
+[[pairs]]
+chain_id = "solana"
+base_token_symbol = "HONEY"
+quote_token_symbol = "USDC"
+base_token_address = "4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy"
+pair_address = "2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo"
+"#;
+
+    #[test]
+    fn test_healthy() {
+        let diagnosis = diagnose(HONEY);
+        assert_eq!(diagnosis.problems, vec![]);
+        assert_eq!(diagnosis.repaired.pairs().len(), 1);
+        let newer = diagnose("version = 99\npairs = []");
+        assert_eq!(newer.problems.len(), 1);
+        assert_eq!(newer.newer_version, Some(99));
+        assert!(newer.problems[0].message.contains("newer tt"));
+        assert_eq!(diagnose("version = 0\npairs = []").newer_version, None);
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let text = format!(
+            "{}\n[[alerts]]\ntoken = \"HONEY\"\nthreshold = 1.x\n",
+            HONEY
+        );
+        let diagnosis = diagnose(&text);
+        assert_eq!(diagnosis.problems.len(), 2);
+        assert_eq!(diagnosis.problems[0].position, Some((11, 15)));
+        assert!(diagnosis.problems[0]
+            .to_string()
+            .starts_with("line 11, column 15: invalid floating-point number"));
+        // The alert lost its threshold, so the entry is dropped too.
+        assert_eq!(diagnosis.problems[1].position, Some((9, 1)));
+        assert!(diagnosis.problems[1]
+            .message
+            .starts_with("alerts entry 1 dropped"));
+        assert_eq!(diagnosis.repaired.pairs().len(), 1);
+        assert!(diagnosis.repaired.alerts().is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_at_end() {
+        for tail in ["pairs = [\n", "x = \"\"\"abc\n", "x = [1,\n2,\n"] {
+            let text = format!("{}\n{}", HONEY, tail);
+            let diagnosis = diagnose(&text);
+            assert_eq!(diagnosis.problems.len(), 1, "{:?}", tail);
+            assert_eq!(diagnosis.repaired.pairs().len(), 1, "{:?}", tail);
+        }
+        assert!(!diagnose("pairs = [\n").problems.is_empty());
+    }
+
+    #[test]
+    fn test_entries() {
+        let text = format!(
+            "{}{}\n[[pairs]]\nchain_id = \"solana\"\n\n[[holdings]]\namount = -1\n\n[extra]\nkey = 1\n",
+            HONEY, HONEY
+        );
+        let messages: Vec<String> = diagnose(&text)
+            .problems
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "line 9, column 1: pairs entry 2 dropped: pair 2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo is pinned twice",
+                "line 16, column 1: pairs entry 3 dropped: missing field `base_token_symbol`",
+                "line 19, column 1: holdings entry 1 dropped: missing field `pair`",
+                "line 22, column 2: unknown key `extra` is dropped",
+            ]
+        );
+        assert_eq!(
+            diagnose("").problems[0].message,
+            "missing `pairs`, an empty list is written"
+        );
+    }
+
+    #[test]
+    fn test_same_address_on_two_chains() {
+        let text = format!("{}{}", HONEY, HONEY.replace("solana", "base"));
+        let diagnosis = diagnose(&text);
+        assert_eq!(diagnosis.problems, vec![]);
+        assert_eq!(diagnosis.repaired.pairs().len(), 2);
+    }
+
+    #[test]
+    fn test_profile_pinned_twice() {
+        let pairs = HONEY.replace("[[pairs]]", "[[profiles.trading.pairs]]");
+        let text = format!(
+            "pairs = []
+{}{}{}",
+            pairs,
+            pairs,
+            pairs.replace("solana", "base")
+        );
+        let diagnosis = diagnose(&text);
+        let messages: Vec<String> = diagnosis.problems.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            ["line 3, column 3: profile `trading` pairs entry 2 dropped: pair 2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo is pinned twice"]
+        );
+        let profiles = diagnosis.repaired.profiles();
+        let chains: Vec<&str> = profiles["trading"]
+            .pairs
+            .iter()
+            .map(|pair| pair.chain_id.as_str())
+            .collect();
+        assert_eq!(chains, ["solana", "base"]);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let text = format!(
+            "format = \"xml\"\n{}\n[profiles.trading]\nformat = \"json\"\n\n[profiles.broken]\npairs = 1\n",
+            HONEY
+        );
+        let diagnosis = diagnose(&text);
+        let messages: Vec<String> = diagnosis.problems.iter().map(ToString::to_string).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("line 1, column 1: `format` is not one of table"));
+        assert!(messages[1].starts_with("line 13, column 2: profile `broken` dropped"));
+        let profiles = diagnosis.repaired.profiles();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles["trading"].format, Some(Format::Json));
+    }
+
+    #[test]
+    fn test_repair() {
+        let dir = TempDir::new("doctor");
+        let path = dir.join("config.toml");
+        let broken = format!("{}\nthreshold = \n", HONEY);
+        fs::write(&path, &broken).unwrap();
+        assert!(Config::load_from(&path).is_err());
+
+        let (diagnosis, backup_path) = repair(&path).unwrap();
+        assert_eq!(diagnosis.problems.len(), 1);
+        let backup_path = backup_path.unwrap();
+        assert!(backup_path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("config.toml.bak-"));
+        assert_eq!(fs::read_to_string(&backup_path).unwrap(), broken);
+        assert_eq!(Config::load_from(&path).unwrap().pairs().len(), 1);
+
+        let (diagnosis, backup_path) = repair(&path).unwrap();
+        assert!(diagnosis.problems.is_empty());
+        assert!(backup_path.is_none());
+
+        // A file from a newer tt is left as it is.
+        let newer = format!("version = 99\n{}", HONEY);
+        fs::write(&path, &newer).unwrap();
+        assert!(matches!(
+            repair(&path),
+            Err(TokenTideError::ConfigVersion { version: 99, .. })
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors returned by token-tide.
+#[derive(Debug)]
+pub enum TokenTideError {
+    /// The request could not be sent or the response could not be read.
+    Network(reqwest::Error),
+    /// The API answered with a non-success status code.
+    HttpStatus(reqwest::StatusCode),
+    /// The API response is not in the expected shape.
+    Decode(serde_json::Error),
+    /// No pair matches the given symbol or address.
+    NotFound(String),
+    /// The config file could not be read or written.
+    ConfigIo { path: PathBuf, source: io::Error },
+    /// The config file is not valid TOML or does not match the config schema.
+    ConfigParse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// The config file is written for a schema version this build does not know.
+    ConfigVersion { path: PathBuf, version: i64 },
+    /// `tt config doctor` found problems in the config file.
+    ConfigInvalid { path: PathBuf, problems: usize },
+    /// The config could not be serialized to TOML.
+    ConfigSerialize(toml::ser::Error),
+    /// Results could not be rendered in the requested output format.
+    Output(String),
+    /// The terminal could not be set up or drawn to.
+    Terminal(io::Error),
+    /// An alert notification could not be delivered.
+    Notify(String),
+    /// Input that parses but cannot be applied, such as selling more than is held.
+    Invalid(String),
+}
+
+/// Result type used throughout token-tide.
+pub type Result<T> = std::result::Result<T, TokenTideError>;
+
+impl TokenTideError {
+    /// Process exit code for this error, so scripts can tell failures apart.
+    ///
+    /// | Code | Error                       |
+    /// |------|-----------------------------|
+    /// | 1    | output or terminal error    |
+    /// | 2    | invalid input               |
+    /// | 3    | token not found             |
+    /// | 4    | network error               |
+    /// | 5    | HTTP error status           |
+    /// | 6    | unexpected API response     |
+    /// | 7    | config file I/O error       |
+    /// | 8    | config file parse error, unknown version or problems found by `tt config doctor` |
+    /// | 10   | notification delivery error |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TokenTideError::NotFound(_) => 3,
+            TokenTideError::Network(_) => 4,
+            TokenTideError::HttpStatus(_) => 5,
+            TokenTideError::Decode(_) => 6,
+            TokenTideError::ConfigIo { .. } | TokenTideError::ConfigSerialize(_) => 7,
+            TokenTideError::ConfigParse { .. }
+            | TokenTideError::ConfigVersion { .. }
+            | TokenTideError::ConfigInvalid { .. } => 8,
+            TokenTideError::Output(_) | TokenTideError::Terminal(_) => 1,
+            TokenTideError::Notify(_) => 10,
+            TokenTideError::Invalid(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for TokenTideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenTideError::Network(err) => write!(f, "Network error: {}", err),
+            TokenTideError::HttpStatus(status)
+                if *status == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+            {
+                write!(
+                    f,
+                    "Rate limited by the API ({}), try again later or lower http.requests_per_minute",
+                    status
+                )
+            }
+            TokenTideError::HttpStatus(status) => write!(f, "Failed to fetch data: {}", status),
+            TokenTideError::Decode(err) => write!(f, "Unexpected API response: {}", err),
+            TokenTideError::NotFound(search) => write!(f, "{} not found", search),
+            TokenTideError::ConfigIo { path, source } => {
+                write!(
+                    f,
+                    "Cannot access config file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            TokenTideError::ConfigParse { path, source } => {
+                write!(f, "Cannot parse config file {}: {}", path.display(), source)
+            }
+            TokenTideError::ConfigVersion { path, version } => {
+                write!(
+                    f,
+                    "Config file {} is version {}, but this tt only knows versions 1 to {}",
+                    path.display(),
+                    version,
+                    crate::migrate::CURRENT_VERSION
+                )
+            }
+            TokenTideError::ConfigInvalid { path, problems } => {
+                write!(
+                    f,
+                    "Config file {} has {} problem(s)",
+                    path.display(),
+                    problems
+                )
+            }
+            TokenTideError::ConfigSerialize(err) => write!(f, "Cannot serialize config: {}", err),
+            TokenTideError::Output(err) => write!(f, "Cannot render output: {}", err),
+            TokenTideError::Terminal(err) => write!(f, "Terminal error: {}", err),
+            TokenTideError::Notify(err) => write!(f, "Notification failed: {}", err),
+            TokenTideError::Invalid(err) => write!(f, "Invalid input: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TokenTideError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TokenTideError::Network(err) => Some(err),
+            TokenTideError::Decode(err) => Some(err),
+            TokenTideError::ConfigIo { source, .. } => Some(source),
+            TokenTideError::ConfigParse { source, .. } => Some(source),
+            TokenTideError::ConfigSerialize(err) => Some(err),
+            TokenTideError::Terminal(err) => Some(err),
+            TokenTideError::HttpStatus(_)
+            | TokenTideError::NotFound(_)
+            | TokenTideError::ConfigVersion { .. }
+            | TokenTideError::ConfigInvalid { .. }
+            | TokenTideError::Output(_)
+            | TokenTideError::Notify(_)
+            | TokenTideError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for TokenTideError {
+    fn from(err: reqwest::Error) -> Self {
+        TokenTideError::Network(err)
+    }
+}
+
+impl From<serde_json::Error> for TokenTideError {
+    fn from(err: serde_json::Error) -> Self {
+        TokenTideError::Decode(err)
+    }
+}
+
+impl From<toml::ser::Error> for TokenTideError {
+    fn from(err: toml::ser::Error) -> Self {
+        TokenTideError::ConfigSerialize(err)
+    }
+}
@@ -0,0 +1,287 @@
+use crate::error::{Result, TokenTideError};
+use crate::pair::Pair;
+use crate::pins;
+use crate::{constants, number};
+use chrono::{Local, TimeZone};
+use prettytable::{row, Table};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A pair's values at the time it was fetched.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// Unix time in seconds.
+    pub timestamp: u64,
+    pub chain_id: String,
+    pub pair_address: String,
+    pub base_token_symbol: String,
+    pub base_token_address: String,
+    pub quote_token_symbol: String,
+    pub price_usd: Option<f64>,
+    pub price_native: Option<f64>,
+    pub volume_h24: f64,
+    pub liquidity_usd: Option<f64>,
+    pub fdv: Option<f64>,
+    pub buys_h24: i32,
+    pub sells_h24: i32,
+}
+
+impl Snapshot {
+    pub fn new(pair: &Pair, timestamp: u64) -> Snapshot {
+        Snapshot {
+            timestamp,
+            chain_id: pair.chain_id.clone(),
+            pair_address: pair.pair_address.clone(),
+            base_token_symbol: pair.base_token.symbol.clone(),
+            base_token_address: pair.base_token.address.clone(),
+            quote_token_symbol: pair.quote_token.symbol.clone(),
+            price_usd: pair
+                .price_usd
+                .as_deref()
+                .and_then(|price| price.parse().ok()),
+            price_native: pair.price_native.parse().ok(),
+            volume_h24: pair.volume.h24,
+            liquidity_usd: pair.liquidity.as_ref().and_then(|value| value.usd),
+            fdv: pair.fdv,
+            buys_h24: pair.txns.h24.buys,
+            sells_h24: pair.txns.h24.sells,
+        }
+    }
+
+    /// The chain and pair address identifying the pair, uppercased, as
+    /// [`PairConfig::key`](crate::config::PairConfig::key) does for pins.
+    pub fn key(&self) -> (String, String) {
+        (
+            self.chain_id.to_uppercase(),
+            self.pair_address.to_uppercase(),
+        )
+    }
+
+    /// Whether the snapshot is of the pair, or of a pair trading the token,
+    /// named by a symbol or address, optionally prefixed as in `solana:HONEY`.
+    pub fn matches(&self, token: &str) -> bool {
+        let (chain_id, token) = pins::split_chain(token);
+        if chain_id.is_some_and(|chain_id| !self.chain_id.eq_ignore_ascii_case(chain_id)) {
+            return false;
+        }
+        let token = token.to_uppercase();
+        self.base_token_symbol.to_uppercase() == token
+            || self.base_token_address.to_uppercase() == token
+            || self.pair_address.to_uppercase() == token
+    }
+}
+
+/// Append-only store of snapshots, one JSON object per line.
+#[derive(Clone, Debug)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new(path: PathBuf) -> History {
+        History { path }
+    }
+
+    /// The history kept next to the config file.
+    pub fn beside(config_path: &Path) -> History {
+        History::new(config_path.with_file_name("history.jsonl"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends a snapshot of the pair taken now.
+    pub fn record(&self, pair: &Pair) -> Result<()> {
+        self.append(&Snapshot::new(pair, unix_now()))
+    }
+
+    pub fn append(&self, snapshot: &Snapshot) -> Result<()> {
+        let io_err = |source| TokenTideError::ConfigIo {
+            path: self.path.clone(),
+            source,
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(io_err)?;
+        }
+        let mut line = serde_json::to_string(snapshot)?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(io_err)?;
+        file.write_all(line.as_bytes()).map_err(io_err)
+    }
+
+    /// Reads the snapshots matching the filter in recorded order. Lines that
+    /// cannot be parsed, e.g. from an interrupted write, are skipped.
+    pub fn read<F>(&self, filter: F) -> Result<Vec<Snapshot>>
+    where
+        F: Fn(&Snapshot) -> bool,
+    {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        let content =
+            fs::read_to_string(&self.path).map_err(|source| TokenTideError::ConfigIo {
+                path: self.path.clone(),
+                source,
+            })?;
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Snapshot>(line).ok())
+            .filter(|snapshot| filter(snapshot))
+            .collect())
+    }
+
+    /// Reads the series of one pair recorded at or after `since`. The chain
+    /// is matched too, as the same address can be a pair on several chains.
+    pub fn series(&self, chain_id: &str, pair_address: &str, since: u64) -> Result<Vec<Snapshot>> {
+        self.read(|snapshot| {
+            snapshot.timestamp >= since
+                && snapshot.chain_id.eq_ignore_ascii_case(chain_id)
+                && snapshot.pair_address.eq_ignore_ascii_case(pair_address)
+        })
+    }
+}
+
+/// Parses a duration such as `45s`, `30m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", value))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" | "" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "Invalid duration unit: {} (use s, m, h, d or w)",
+                unit
+            ))
+        }
+    };
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration out of range: {}", value))
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Formats a unix timestamp in local time.
+pub fn format_time(timestamp: u64) -> String {
+    Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or(timestamp.to_string())
+}
+
+/// Builds the table printed by `tt history`.
+pub fn history_table(snapshots: &[Snapshot]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Time",
+        "Price In USD",
+        "Price Native",
+        "24h Volume",
+        "Liquidity",
+        "FDV",
+        "24h Buys/Sells"
+    ]);
+    let optional = |value: Option<f64>, format: fn(f64) -> String| {
+        value.map(format).unwrap_or(constants::NONE_STR.to_string())
+    };
+    for snapshot in snapshots {
+        table.add_row(row![
+            format_time(snapshot.timestamp),
+            optional(snapshot.price_usd, |value| value.to_string()),
+            optional(snapshot.price_native, |value| value.to_string()),
+            number::format_with_unit(snapshot.volume_h24),
+            optional(snapshot.liquidity_usd, |value| format!(
+                "${}",
+                number::format_with_unit(value)
+            )),
+            optional(snapshot.fdv, number::format_with_unit),
+            format!("{}/{}", snapshot.buys_h24, snapshot.sells_h24)
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45s"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(7 * 86400)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(14 * 86400)));
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("d").is_err());
+        assert_eq!(
+            parse_duration("99999999999999999w"),
+            Err("Duration out of range: 99999999999999999w".to_string())
+        );
+    }
+
+    #[test]
+    fn test_append_and_series() {
+        let dir = TempDir::new("series");
+        let history = History::new(dir.join("history.jsonl"));
+        let pair = honey_pair();
+        history.append(&Snapshot::new(&pair, 100)).unwrap();
+        history.append(&Snapshot::new(&pair, 200)).unwrap();
+        let mut other = pair.clone();
+        other.pair_address = "other".to_string();
+        history.append(&Snapshot::new(&other, 300)).unwrap();
+        let mut other_chain = pair.clone();
+        other_chain.chain_id = "base".to_string();
+        history.append(&Snapshot::new(&other_chain, 400)).unwrap();
+
+        let series = history
+            .series(&pair.chain_id, &pair.pair_address, 150)
+            .unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].timestamp, 200);
+        assert_eq!(series[0].price_usd, Some(0.1735));
+        assert_eq!(series[0].buys_h24, 300);
+
+        let honey = history.read(|snapshot| snapshot.matches("honey")).unwrap();
+        assert_eq!(honey.len(), 4);
+    }
+
+    #[test]
+    fn test_read_skips_broken_lines() {
+        let dir = TempDir::new("broken");
+        let history = History::new(dir.join("history.jsonl"));
+        history.append(&Snapshot::new(&honey_pair(), 100)).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(history.path())
+            .unwrap();
+        file.write_all(b"{\"timestamp\": 1").unwrap();
+        assert_eq!(history.read(|_| true).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,425 @@
+use crate::error::{Result, TokenTideError};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const USER_AGENT: &str = concat!("token-tide/", env!("CARGO_PKG_VERSION"));
+
+/// Longest a single retry waits, whatever `Retry-After` asks for.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// HTTP settings stored in the `[http]` table of the config.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct HttpSettings {
+    /// Requests allowed per minute, 0 to disable the limiter. DexScreener
+    /// allows 300 per minute on the pair and search endpoints.
+    pub requests_per_minute: u32,
+    /// Retries after a 429, a 5xx or a timeout.
+    pub retries: u32,
+    /// Timeout of a whole request, from connecting to reading the body.
+    #[serde(deserialize_with = "at_least_one_second")]
+    pub timeout_secs: u64,
+    #[serde(deserialize_with = "at_least_one_second")]
+    pub connect_timeout_secs: u64,
+    /// Proxy for every request, e.g. `http://proxy:3128` or
+    /// `socks5://127.0.0.1:1080`. Without one, `HTTPS_PROXY` and friends are
+    /// honored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Comma separated hosts that bypass the proxy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// PEM file of extra root certificates to trust, e.g. a corporate CA.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<PathBuf>,
+    /// API base URL replacing `https://api.dexscreener.io`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        HttpSettings {
+            requests_per_minute: 300,
+            retries: 3,
+            timeout_secs: 30,
+            connect_timeout_secs: 10,
+            proxy: None,
+            no_proxy: None,
+            user_agent: None,
+            ca_bundle: None,
+            base_url: None,
+        }
+    }
+}
+
+/// Token bucket allowing bursts of up to `requests` and refilling at
+/// `requests` per `per`.
+pub struct RateLimiter {
+    capacity: f64,
+    per_second: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(requests: u32, per: Duration) -> RateLimiter {
+        let capacity = requests.max(1) as f64;
+        RateLimiter {
+            capacity,
+            per_second: capacity / per.as_secs_f64(),
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    /// Takes a token, waiting until one is available.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut state = self.state.lock().expect("rate limiter lock");
+            let (tokens, last) = *state;
+            let now = Instant::now();
+            let refilled = tokens + now.duration_since(last).as_secs_f64() * self.per_second;
+            // Going below zero reserves a future token for this caller.
+            let left = refilled.min(self.capacity) - 1.0;
+            *state = (left, now);
+            if left < 0.0 {
+                Duration::from_secs_f64(-left / self.per_second)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Reads a timeout, refusing 0, with which every request would time out at once.
+fn at_least_one_second<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<u64, D::Error> {
+    match u64::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("a timeout must be at least 1 second")),
+        secs => Ok(secs),
+    }
+}
+
+/// HTTP client shared by every request of a source, so concurrent lookups
+/// draw from one rate limit.
+pub struct HttpClient {
+    client: reqwest::Client,
+    limiter: Option<RateLimiter>,
+    retries: u32,
+    base_delay: Duration,
+}
+
+impl HttpClient {
+    /// Builds the client once from the settings, so every request shares its
+    /// connection pool, proxy and certificates.
+    pub fn new(settings: &HttpSettings) -> Result<HttpClient> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(settings.timeout_secs))
+            .connect_timeout(Duration::from_secs(settings.connect_timeout_secs))
+            .user_agent(
+                settings
+                    .user_agent
+                    .clone()
+                    .unwrap_or_else(|| USER_AGENT.to_string()),
+            );
+        if let Some(url) = &settings.proxy {
+            let mut proxy = reqwest::Proxy::all(url)
+                .map_err(|err| TokenTideError::Invalid(format!("http.proxy {}: {}", url, err)))?;
+            if let Some(hosts) = &settings.no_proxy {
+                proxy = proxy.no_proxy(reqwest::NoProxy::from_string(hosts));
+            }
+            builder = builder.proxy(proxy);
+        }
+        if let Some(path) = &settings.ca_bundle {
+            for certificate in read_certificates(path)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        let client = builder.build()?;
+        let limiter = (settings.requests_per_minute > 0)
+            .then(|| RateLimiter::new(settings.requests_per_minute, Duration::from_secs(60)));
+        Ok(HttpClient {
+            client,
+            limiter,
+            retries: settings.retries,
+            base_delay: Duration::from_millis(500),
+        })
+    }
+
+    /// The underlying client, for requests that are not rate limited or
+    /// retried, such as notifications.
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Sets the delay before the first retry, doubled for each further one.
+    pub fn with_base_delay(mut self, delay: Duration) -> HttpClient {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Gets the body of a URL. 429 and 5xx responses, timeouts and refused
+    /// connections are retried with exponential backoff and jitter, or after
+    /// the `Retry-After` the server sent.
+    pub async fn get(&self, url: &str) -> Result<String> {
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire().await;
+            }
+            let can_retry = attempt < self.retries;
+            let delay = match self.client.get(url).send().await {
+                Ok(response) if response.status().is_success() => {
+                    return Ok(response.text().await?);
+                }
+                Ok(response) if can_retry && is_retryable(response.status()) => {
+                    retry_after(response.headers()).unwrap_or_else(|| self.backoff(attempt))
+                }
+                Ok(response) => return Err(TokenTideError::HttpStatus(response.status())),
+                Err(err) if can_retry && (err.is_timeout() || err.is_connect()) => {
+                    self.backoff(attempt)
+                }
+                Err(err) => return Err(err.into()),
+            };
+            tokio::time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Exponential backoff with jitter, between half and all of
+    /// `base_delay * 2^attempt`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        let jitter = (RandomState::new().hash_one(attempt) % 1000) as f64 / 1000.0;
+        delay.mul_f64(0.5 + jitter / 2.0)
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        HttpClient::new(&HttpSettings::default()).expect("default HTTP client")
+    }
+}
+
+/// Reads every certificate of a PEM bundle.
+fn read_certificates(path: &Path) -> Result<Vec<reqwest::Certificate>> {
+    let pem = fs::read_to_string(path).map_err(|source| TokenTideError::ConfigIo {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    let certificates = pem
+        .match_indices(BEGIN)
+        .map(|(start, _)| {
+            let end = pem[start..]
+                .find("-----END CERTIFICATE-----")
+                .map_or(pem.len(), |end| {
+                    start + end + "-----END CERTIFICATE-----".len()
+                });
+            reqwest::Certificate::from_pem(&pem.as_bytes()[start..end]).map_err(|err| {
+                TokenTideError::Invalid(format!("http.ca_bundle {}: {}", path.display(), err))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if certificates.is_empty() {
+        return Err(TokenTideError::Invalid(format!(
+            "http.ca_bundle {}: no certificates found",
+            path.display()
+        )));
+    }
+    Ok(certificates)
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Reads `Retry-After` as seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let seconds = date.timestamp() - chrono::Utc::now().timestamp();
+    Some(Duration::from_secs(seconds.max(0) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves one canned response per connection and returns how many it
+    /// served.
+    /// Serves the canned responses one connection each and returns the
+    /// requests received.
+    async fn stand_in(
+        responses: Vec<&'static str>,
+    ) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/latest", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = vec![];
+            for response in &responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 4096];
+                while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                    let read = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..read]);
+                }
+                socket.write_all(response.as_bytes()).await.unwrap();
+                requests.push(String::from_utf8_lossy(&request).into_owned());
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn client(retries: u32) -> HttpClient {
+        HttpClient::new(&HttpSettings {
+            retries,
+            ..HttpSettings::default()
+        })
+        .unwrap()
+        .with_base_delay(Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let (url, handle) = stand_in(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nretry-after: 0\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            "HTTP/1.1 503 Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}",
+        ])
+        .await;
+        assert_eq!(client(3).get(&url).await.unwrap(), "{}");
+        assert_eq!(handle.await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted() {
+        let (url, _handle) = stand_in(vec![
+            "HTTP/1.1 429 Too Many Requests\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            "HTTP/1.1 429 Too Many Requests\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        ])
+        .await;
+        let err = client(1).get(&url).await.unwrap_err();
+        assert!(matches!(
+            err,
+            TokenTideError::HttpStatus(StatusCode::TOO_MANY_REQUESTS)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_not_found_is_not_retried() {
+        let (url, handle) = stand_in(vec![
+            "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        ])
+        .await;
+        let err = client(3).get(&url).await.unwrap_err();
+        assert!(matches!(
+            err,
+            TokenTideError::HttpStatus(StatusCode::NOT_FOUND)
+        ));
+        assert_eq!(handle.await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_settings() {
+        let (url, handle) = stand_in(vec![
+            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+        ])
+        .await;
+        // The stand-in plays the proxy, so a request to any host reaches it.
+        let proxy = url.trim_end_matches("/latest").to_string();
+        let client = HttpClient::new(&HttpSettings {
+            proxy: Some(proxy),
+            user_agent: Some("desk-bot".to_string()),
+            ..HttpSettings::default()
+        })
+        .unwrap();
+        assert_eq!(client.get("http://example.invalid/").await.unwrap(), "ok");
+        let requests = handle.await.unwrap();
+        assert!(requests[0].starts_with("GET http://example.invalid/ "));
+        assert!(requests[0]
+            .to_lowercase()
+            .contains("\r\nuser-agent: desk-bot\r\n"));
+
+        let invalid = HttpSettings {
+            proxy: Some("not a url".to_string()),
+            ..HttpSettings::default()
+        };
+        assert!(matches!(
+            HttpClient::new(&invalid),
+            Err(TokenTideError::Invalid(_))
+        ));
+        let missing = HttpSettings {
+            ca_bundle: Some(PathBuf::from("/nonexistent/ca.pem")),
+            ..HttpSettings::default()
+        };
+        assert!(matches!(
+            HttpClient::new(&missing),
+            Err(TokenTideError::ConfigIo { .. })
+        ));
+    }
+
+    #[test]
+    fn test_zero_timeout_is_refused() {
+        let settings: HttpSettings = toml::from_str("timeout_secs = 5").unwrap();
+        assert_eq!(settings.timeout_secs, 5);
+        for text in ["timeout_secs = 0", "connect_timeout_secs = 0"] {
+            let err = toml::from_str::<HttpSettings>(text).unwrap_err();
+            assert!(err.message().contains("at least 1 second"), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2, Duration::from_millis(200));
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let client = client(3).with_base_delay(Duration::from_millis(100));
+        for attempt in 0..4 {
+            let delay = client.backoff(attempt);
+            let full = Duration::from_millis(100 * 2u64.pow(attempt));
+            assert!(delay >= full / 2 && delay <= full);
+        }
+    }
+}
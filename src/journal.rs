@@ -0,0 +1,399 @@
+use crate::config::PairConfig;
+use crate::error::{Result, TokenTideError};
+use crate::history::Snapshot;
+use crate::pnl::{Book, Lot, Method, Side, Transaction};
+use chrono::{Local, NaiveDate, TimeZone};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+const HOLDINGS_ACCOUNT: &str = "Assets:Crypto";
+const CASH_ACCOUNT: &str = "Assets:Cash";
+const GAINS_ACCOUNT: &str = "Income:Crypto:Gains";
+
+/// Plain-text accounting syntax to export to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Journal {
+    Beancount,
+    Ledger,
+}
+
+impl Journal {
+    pub const NAMES: [&'static str; 2] = ["beancount", "ledger"];
+
+    fn date(&self, date: NaiveDate) -> String {
+        match self {
+            Journal::Beancount => date.format("%Y-%m-%d").to_string(),
+            Journal::Ledger => date.format("%Y/%m/%d").to_string(),
+        }
+    }
+
+    /// Ledger needs commodities that are not plain letters quoted.
+    fn commodity(&self, name: &str) -> String {
+        match self {
+            Journal::Ledger if !name.chars().all(|c| c.is_ascii_alphabetic()) => {
+                format!("\"{}\"", name)
+            }
+            _ => name.to_string(),
+        }
+    }
+
+    fn amount(&self, amount: f64, commodity: &str) -> String {
+        format!("{} {}", amount, self.commodity(commodity))
+    }
+
+    fn usd(&self, amount: f64) -> String {
+        match self {
+            Journal::Beancount => format!("{} USD", amount),
+            Journal::Ledger if amount < 0.0 => format!("-${}", -amount),
+            Journal::Ledger => format!("${}", amount),
+        }
+    }
+}
+
+impl FromStr for Journal {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "beancount" | "bean" => Ok(Journal::Beancount),
+            "ledger" => Ok(Journal::Ledger),
+            _ => Err(format!(
+                "Unknown journal: {} (expected one of {})",
+                value,
+                Journal::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Journal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Journal::Beancount => write!(f, "beancount"),
+            Journal::Ledger => write!(f, "ledger"),
+        }
+    }
+}
+
+fn token_key(pair: &PairConfig) -> (String, String) {
+    (
+        pair.chain_id.to_uppercase(),
+        pair.base_token_address.to_uppercase(),
+    )
+}
+
+/// Uppercases a name and replaces what commodity names cannot contain.
+/// Beancount allows at most 24 characters, starting with a letter.
+fn sanitize(name: &str) -> String {
+    let mut name: String = name
+        .to_uppercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, 'T');
+    }
+    name.truncate(24);
+    name.trim_end_matches('-').to_string()
+}
+
+/// Derives a commodity name per token from its symbol. Tokens sharing a
+/// symbol get their chain and the start of their address appended, e.g.
+/// `USDC-SOLANA-EPJF`, so each stays a separate commodity.
+pub fn commodities<'a>(
+    pairs: impl IntoIterator<Item = &'a PairConfig>,
+) -> HashMap<(String, String), String> {
+    let mut by_symbol: BTreeMap<String, Vec<&PairConfig>> = BTreeMap::new();
+    for pair in pairs {
+        let tokens = by_symbol
+            .entry(sanitize(&pair.base_token_symbol))
+            .or_default();
+        if !tokens
+            .iter()
+            .any(|value| token_key(value) == token_key(pair))
+        {
+            tokens.push(pair);
+        }
+    }
+    let mut names = HashMap::new();
+    for (symbol, tokens) in by_symbol {
+        let collides = tokens.len() > 1;
+        for pair in tokens {
+            let name = if collides {
+                let address: String = pair.base_token_address.chars().take(4).collect();
+                sanitize(&format!("{}-{}-{}", symbol, pair.chain_id, address))
+            } else {
+                symbol.clone()
+            };
+            names.insert(token_key(pair), name);
+        }
+    }
+    names
+}
+
+/// The last recorded price of each pair, keyed by chain and pair address, on
+/// each local day.
+pub fn closing_prices(snapshots: &[Snapshot]) -> Vec<(NaiveDate, (String, String), f64)> {
+    let mut closes: BTreeMap<(NaiveDate, (String, String)), (u64, f64)> = BTreeMap::new();
+    for snapshot in snapshots {
+        let Some(price) = snapshot.price_usd else {
+            continue;
+        };
+        let Some(date) = Local
+            .timestamp_opt(snapshot.timestamp as i64, 0)
+            .single()
+            .map(|time| time.date_naive())
+        else {
+            continue;
+        };
+        let key = (date, snapshot.key());
+        match closes.get(&key) {
+            Some((timestamp, _)) if *timestamp > snapshot.timestamp => {}
+            _ => {
+                closes.insert(key, (snapshot.timestamp, price));
+            }
+        }
+    }
+    closes
+        .into_iter()
+        .map(|((date, pair), (_, price))| (date, pair, price))
+        .collect()
+}
+
+/// Writes the transactions as a journal, followed by end-of-day price
+/// directives for the traded tokens from the recorded snapshots.
+///
+/// Sells post the lots they consume at their cost, matched by `method`, and
+/// book the difference to an income account. Average cost has no lots to
+/// post, so only FIFO and LIFO are supported.
+pub fn export(
+    journal: Journal,
+    transactions: &[Transaction],
+    snapshots: &[Snapshot],
+    method: Method,
+) -> Result<String> {
+    if method == Method::Average {
+        return Err(TokenTideError::Invalid(
+            "average cost cannot be exported as lots, use fifo or lifo".to_string(),
+        ));
+    }
+    let mut sorted: Vec<&Transaction> = transactions.iter().collect();
+    sorted.sort_by_key(|transaction| transaction.date);
+    let names = commodities(sorted.iter().map(|transaction| &transaction.pair));
+
+    let mut out = String::new();
+    let first = sorted.first().map(|transaction| transaction.date);
+    if journal == Journal::Beancount {
+        out.push_str("option \"operating_currency\" \"USD\"\n\n");
+        if let Some(first) = first {
+            let mut accounts: Vec<String> = names
+                .values()
+                .map(|name| format!("{}:{}", HOLDINGS_ACCOUNT, name))
+                .collect();
+            accounts.sort();
+            accounts.push(CASH_ACCOUNT.to_string());
+            accounts.push(GAINS_ACCOUNT.to_string());
+            for account in accounts {
+                let _ = writeln!(out, "{} open {}", journal.date(first), account);
+            }
+            out.push('\n');
+        }
+    }
+
+    let mut books: HashMap<(String, String), Book> = HashMap::new();
+    for transaction in sorted {
+        let key = token_key(&transaction.pair);
+        let name = &names[&key];
+        let account = format!("{}:{}", HOLDINGS_ACCOUNT, name);
+        let book = books
+            .entry(key)
+            .or_insert_with(|| Book::new(transaction.pair.clone(), method));
+        let consumed = book.apply(transaction)?;
+        let narration = format!(
+            "{} {}",
+            if transaction.side == Side::Buy {
+                "Buy"
+            } else {
+                "Sell"
+            },
+            transaction.pair.base_token_symbol
+        );
+        let _ = match journal {
+            Journal::Beancount => writeln!(
+                out,
+                "{} * \"{}\"",
+                journal.date(transaction.date),
+                narration.replace('"', "'")
+            ),
+            Journal::Ledger => writeln!(out, "{} {}", journal.date(transaction.date), narration),
+        };
+        match transaction.side {
+            Side::Buy => {
+                let lot = Lot {
+                    date: transaction.date,
+                    amount: transaction.amount,
+                    price_usd: transaction.price_usd,
+                };
+                let _ = writeln!(out, "  {}  {}", account, posting(journal, name, &lot, 1.0));
+                let _ = writeln!(out, "  {}", CASH_ACCOUNT);
+            }
+            Side::Sell => {
+                for lot in &consumed {
+                    let _ = writeln!(
+                        out,
+                        "  {}  {} @ {}",
+                        account,
+                        posting(journal, name, lot, -1.0),
+                        journal.usd(transaction.price_usd)
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "  {}  {}",
+                    CASH_ACCOUNT,
+                    journal.usd(transaction.amount * transaction.price_usd)
+                );
+                let _ = writeln!(out, "  {}", GAINS_ACCOUNT);
+            }
+        }
+        out.push('\n');
+    }
+
+    // Every pool a token was traded in prices its commodity, once a day.
+    let pairs: HashMap<(String, String), &String> = transactions
+        .iter()
+        .map(|transaction| {
+            (
+                transaction.pair.key(),
+                &names[&token_key(&transaction.pair)],
+            )
+        })
+        .collect();
+    let mut priced = HashSet::new();
+    for (date, pair, price) in closing_prices(snapshots) {
+        let Some(name) = pairs.get(&pair) else {
+            continue;
+        };
+        if !priced.insert((date, *name)) {
+            continue;
+        }
+        let _ = match journal {
+            Journal::Beancount => writeln!(
+                out,
+                "{} price {} {}",
+                journal.date(date),
+                name,
+                journal.usd(price)
+            ),
+            Journal::Ledger => writeln!(
+                out,
+                "P {} {} {}",
+                journal.date(date),
+                journal.commodity(name),
+                journal.usd(price)
+            ),
+        };
+    }
+    Ok(out)
+}
+
+/// An amount of a lot at its cost, negated for sells.
+fn posting(journal: Journal, commodity: &str, lot: &Lot, sign: f64) -> String {
+    format!(
+        "{} {{{}}}",
+        journal.amount(sign * lot.amount, commodity),
+        journal.usd(lot.price_usd)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+    use crate::pnl::parse_date;
+
+    fn transaction(date: &str, side: Side, amount: f64, price_usd: f64) -> Transaction {
+        Transaction {
+            date: parse_date(date).unwrap(),
+            side,
+            amount,
+            price_usd,
+            pair: PairConfig::from(honey_pair()),
+        }
+    }
+
+    fn trades() -> Vec<Transaction> {
+        vec![
+            transaction("2024-01-01", Side::Buy, 100.0, 1.0),
+            transaction("2024-02-01", Side::Buy, 100.0, 2.0),
+            transaction("2024-03-01", Side::Sell, 150.0, 3.0),
+        ]
+    }
+
+    #[test]
+    fn test_commodities() {
+        let honey = PairConfig::from(honey_pair());
+        let mut other = honey.clone();
+        other.chain_id = "base".to_string();
+        other.base_token_address = "0xabcdef".to_string();
+        let mut odd = honey.clone();
+        odd.base_token_symbol = "$wif".to_string();
+        odd.base_token_address = "wif".to_string();
+
+        let names = commodities([&honey]);
+        assert_eq!(names[&token_key(&honey)], "HONEY");
+
+        let names = commodities([&honey, &other, &odd, &honey]);
+        assert_eq!(names.len(), 3);
+        assert!(names[&token_key(&honey)].starts_with("HONEY-SOLANA-"));
+        assert_eq!(names[&token_key(&other)], "HONEY-BASE-0XAB");
+        assert_eq!(names[&token_key(&odd)], "T-WIF");
+    }
+
+    #[test]
+    fn test_beancount() {
+        let mut snapshot = Snapshot::new(&honey_pair(), 1_709_294_400);
+        snapshot.price_usd = Some(2.5);
+        let journal = export(Journal::Beancount, &trades(), &[snapshot], Method::Fifo).unwrap();
+        assert!(journal.contains("2024-01-01 open Assets:Crypto:HONEY\n"));
+        assert!(journal.contains(
+            "2024-03-01 * \"Sell HONEY\"\n  \
+             Assets:Crypto:HONEY  -100 HONEY {1 USD} @ 3 USD\n  \
+             Assets:Crypto:HONEY  -50 HONEY {2 USD} @ 3 USD\n  \
+             Assets:Cash  450 USD\n  \
+             Income:Crypto:Gains\n"
+        ));
+        assert!(journal.contains(" price HONEY 2.5 USD\n"));
+
+        // Every pool traded prices the token, but the same address on another
+        // chain prices nothing.
+        let mut two_pools = trades();
+        two_pools[1].pair.pair_address = "other_pool".to_string();
+        let mut other_pool = honey_pair();
+        other_pool.pair_address = "other_pool".to_string();
+        let mut other_chain = honey_pair();
+        other_chain.chain_id = "base".to_string();
+        let snapshots: Vec<Snapshot> = [(other_pool, 1_709_294_400), (other_chain, 1_709_380_800)]
+            .iter()
+            .map(|(pair, timestamp)| Snapshot::new(pair, *timestamp))
+            .collect();
+        let journal = export(Journal::Beancount, &two_pools, &snapshots, Method::Fifo).unwrap();
+        assert_eq!(journal.matches(" price HONEY ").count(), 1);
+        assert!(export(Journal::Beancount, &trades(), &[], Method::Average).is_err());
+    }
+
+    #[test]
+    fn test_ledger() {
+        let mut trades = trades();
+        for trade in trades.iter_mut() {
+            trade.pair.base_token_symbol = "HONEY2".to_string();
+        }
+        let journal = export(Journal::Ledger, &trades, &[], Method::Lifo).unwrap();
+        assert!(journal.starts_with(
+            "2024/01/01 Buy HONEY2\n  Assets:Crypto:HONEY2  100 \"HONEY2\" {$1}\n  Assets:Cash\n"
+        ));
+        assert!(journal.contains("-100 \"HONEY2\" {$2} @ $3\n"));
+    }
+}
@@ -0,0 +1,51 @@
+//! Token Tide looks up token prices and pairs on decentralized exchanges.
+//!
+//! The [`TokenTide`] client resolves symbols or addresses to pairs through a
+//! [`PriceSource`] backend (DexScreener by default) and keeps the pairs it has
+//! seen pinned in a [`Config`], so later lookups stay on the same pool.
+//!
+//! ```no_run
+//! use token_tide::{Config, TokenTide};
+//!
+//! # async fn run() -> token_tide::Result<()> {
+//! let client = TokenTide::new();
+//! let mut config = Config::load()?;
+//! let pair = client.query(&mut config, "honey").await?;
+//! println!("{}: {:?}", pair.base_token.symbol, pair.price_usd);
+//! # Ok(())
+//! # }
+//! ```
+pub mod alert;
+pub mod cache;
+pub mod chart;
+pub mod client;
+pub mod config;
+pub mod constants;
+pub mod dash;
+pub mod dexscreener;
+pub mod doctor;
+pub mod error;
+pub mod history;
+pub mod http;
+pub mod journal;
+pub mod migrate;
+pub mod notify;
+pub mod number;
+pub mod output;
+pub mod pair;
+pub mod pins;
+pub mod pnl;
+pub mod portfolio;
+pub mod profile;
+pub mod source;
+pub mod table;
+#[cfg(test)]
+mod test_util;
+pub mod watch;
+
+pub use client::{AddOutcome, TokenTide};
+pub use config::{Config, PairConfig};
+pub use dexscreener::DexScreener;
+pub use error::{Result, TokenTideError};
+pub use pair::Pair;
+pub use source::PriceSource;
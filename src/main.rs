@@ -1,29 +1,825 @@
-#![allow(dead_code)]
 mod command;
-mod config;
-mod constants;
-mod dexscreener;
-mod number;
+
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+use token_tide::alert::{self, AlertRule, AlertStatus, Condition, Metric};
+use token_tide::cache::{self, Cache, CacheMode};
+use token_tide::chart::{self, ChartOptions};
+use token_tide::dash;
+use token_tide::dexscreener;
+use token_tide::doctor;
+use token_tide::history::{self, History, Snapshot};
+use token_tide::http::HttpClient;
+use token_tide::journal::{self, Journal};
+use token_tide::notify::{self, Delivery, Notifier, NotifyState};
+use token_tide::output::{self, Format};
+use token_tide::pins;
+use token_tide::pnl::{self, Side, Transaction};
+use token_tide::portfolio;
+use token_tide::profile;
+use token_tide::watch::{self, Watcher};
+use token_tide::{Config, DexScreener, PairConfig, Result, TokenTide, TokenTideError};
 
 #[tokio::main]
 async fn main() {
+    if let Err(err) = run().await {
+        eprintln!("Error: {}", err);
+        if let TokenTideError::ConfigParse { .. } = err {
+            eprintln!(
+                "Run `tt config doctor` to list every problem, or add `--fix` to repair the file."
+            );
+        }
+        process::exit(err.exit_code());
+    }
+}
+
+async fn run() -> Result<()> {
     let matches = command::tt_command().get_matches();
+    let config_path = match matches.get_one::<PathBuf>("config") {
+        Some(path) => path.clone(),
+        None => Config::path()?,
+    };
+    // The doctor reads the file itself, since it has to cope with a broken one.
+    if let Some(("config", sub_matches)) = matches.subcommand() {
+        return config_command(&config_path, sub_matches);
+    }
+    let mut config = Config::load_from(&config_path)?;
+    let profile = matches
+        .get_one::<String>("profile")
+        .cloned()
+        .or_else(|| env::var(profile::PROFILE_ENV).ok())
+        .filter(|name| !name.is_empty());
+    if let Some(name) = &profile {
+        config.use_profile(name)?;
+    }
+    let mode = cache_mode(&matches);
+    let cache = Cache::beside(
+        &config_path,
+        Duration::from_secs(config.cache().ttl_secs),
+        mode,
+    );
+    let source = DexScreener::with_http(HttpClient::new(config.http())?)
+        .with_host(&dexscreener::resolve_host(
+            config.http().base_url.as_deref(),
+        ))
+        .with_cache(cache);
+    // Cached data is not new, so the client only records what the source
+    // fetched from the API.
+    let client = TokenTide::with_source(source).with_history(History::beside(&config_path));
+    let format: Format = match matches.value_source("format") {
+        Some(ValueSource::CommandLine) => matches
+            .get_one::<String>("format")
+            .expect("given")
+            .parse()
+            .expect("validated by clap"),
+        _ => config.format().unwrap_or_default(),
+    };
+    let result = dispatch(&client, &mut config, &matches, format).await;
+    if mode == CacheMode::Offline {
+        if let Some(fetched_at) = client.source().cache().and_then(Cache::oldest_served) {
+            eprintln!(
+                "Offline: showing data cached {} ago",
+                cache::format_age(history::unix_now().saturating_sub(fetched_at))
+            );
+        }
+    }
+    result
+}
+
+/// Picks the cache mode from the global flags. Live views always ask the API
+/// unless offline, since their refresh interval can be shorter than the TTL.
+fn cache_mode(matches: &ArgMatches) -> CacheMode {
+    if matches.get_flag("offline") {
+        CacheMode::Offline
+    } else if matches.get_flag("no-cache") {
+        CacheMode::Bypass
+    } else if matches.get_flag("refresh")
+        || matches!(matches.subcommand_name(), Some("watch" | "dash"))
+    {
+        CacheMode::Refresh
+    } else {
+        CacheMode::Normal
+    }
+}
+
+async fn dispatch(
+    client: &TokenTide,
+    config: &mut Config,
+    matches: &ArgMatches,
+    format: Format,
+) -> Result<()> {
     match matches.subcommand() {
         Some(("list", sub_matches)) => {
             let token = sub_matches.get_one::<String>("TOKEN").expect("required");
-            println!("Searching {} ...", token.to_uppercase());
-            dexscreener::search(token).await
+            searching(token, format);
+            search(client, token, format).await
         }
         Some(("query", sub_matches)) => {
             let simple = sub_matches.get_one::<bool>("simple").unwrap_or(&false);
-            let token = sub_matches.get_one::<String>("TOKEN").expect("required");
-            println!("Searching {} ...", token.to_uppercase());
-            dexscreener::query(token, &simple).await
+            let jobs = *sub_matches.get_one::<u64>("jobs").expect("default") as usize;
+            let mut tokens: Vec<String> = sub_matches
+                .get_many::<String>("TOKEN")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            if tokens.is_empty() || tokens == ["-"] {
+                tokens = read_tokens()?;
+            }
+            searching(&tokens.join(", "), format);
+            match tokens.as_slice() {
+                [token] => query(client, config, token, *simple, format).await,
+                _ => query_many(client, config, &tokens, jobs, format).await,
+            }
         }
         Some(("add", sub_matches)) => {
             let address = sub_matches.get_one::<String>("ADDRESS").expect("required");
-            dexscreener::add(address).await;
+            add(client, config, address).await
+        }
+        Some(("watch", sub_matches)) => {
+            let tokens: Vec<String> = sub_matches
+                .get_many::<String>("TOKEN")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            let interval = *sub_matches.get_one::<u64>("interval").expect("default");
+            watch(client, config, &tokens, Duration::from_secs(interval)).await
+        }
+        Some(("dash", sub_matches)) => {
+            let interval = *sub_matches.get_one::<u64>("interval").expect("default");
+            dash::run(client, config, Duration::from_secs(interval)).await
+        }
+        Some(("alert", sub_matches)) => alert(client, config, sub_matches, format).await,
+        Some(("history", sub_matches)) => {
+            let token = sub_matches.get_one::<String>("TOKEN").expect("required");
+            let since = *sub_matches.get_one::<Duration>("since").expect("default");
+            print_history(config, token, since, format)
+        }
+        Some(("chart", sub_matches)) => {
+            let token = sub_matches.get_one::<String>("TOKEN").expect("required");
+            let since = *sub_matches.get_one::<Duration>("since").expect("default");
+            let options = ChartOptions {
+                style: sub_matches
+                    .get_one::<String>("style")
+                    .expect("default")
+                    .parse()
+                    .expect("validated by clap"),
+                width: *sub_matches.get_one::<u64>("width").expect("default") as usize,
+                height: *sub_matches.get_one::<u64>("height").expect("default") as usize,
+                volume: sub_matches.get_flag("volume"),
+            };
+            print_chart(config, token, since, options)
+        }
+        Some(("portfolio", sub_matches)) => portfolio(client, config, sub_matches, format).await,
+        Some(("pins", sub_matches)) => pins(config, sub_matches, format),
+        Some(("profile", sub_matches)) => profile_command(config, sub_matches, format),
+        _ => unreachable!(),
+    }
+}
+
+/// Announces a lookup, only for tables so machine-readable output stays clean.
+fn searching(token: &str, format: Format) {
+    if format == Format::Table {
+        println!("Searching {} ...", token.to_uppercase());
+    }
+}
+
+async fn search(client: &TokenTide, token: &str, format: Format) -> Result<()> {
+    let pairs = client.search(token).await?;
+    print!("{}", output::render_pairs(&pairs, format)?);
+    Ok(())
+}
+
+async fn query(
+    client: &TokenTide,
+    config: &mut Config,
+    token: &str,
+    simple: bool,
+    format: Format,
+) -> Result<()> {
+    let pair = client.query(config, token).await?;
+    print!("{}", output::render_pair(&pair, simple, format)?);
+    Ok(())
+}
+
+/// Reads whitespace separated tokens from stdin, skipping `#` comments.
+fn read_tokens() -> Result<Vec<String>> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Err(TokenTideError::Invalid(
+            "no tokens given, pass them as arguments or on stdin".to_string(),
+        ));
+    }
+    let mut tokens = vec![];
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|err| TokenTideError::Invalid(err.to_string()))?;
+        let line = line.split('#').next().unwrap_or_default();
+        tokens.extend(line.split_whitespace().map(str::to_string));
+    }
+    if tokens.is_empty() {
+        return Err(TokenTideError::Invalid("no tokens on stdin".to_string()));
+    }
+    Ok(tokens)
+}
+
+/// Queries every token concurrently and prints the pairs found in one table.
+/// Tokens that fail are reported on stderr, the last one as the error.
+async fn query_many(
+    client: &TokenTide,
+    config: &mut Config,
+    tokens: &[String],
+    jobs: usize,
+    format: Format,
+) -> Result<()> {
+    let results = client.query_many(config, tokens, jobs).await?;
+    let mut pairs = vec![];
+    let mut errors = vec![];
+    for result in results {
+        match result {
+            Ok(pair) => pairs.push(pair),
+            Err(err) => errors.push(err),
+        }
+    }
+    if !pairs.is_empty() {
+        print!("{}", output::render_pairs(&pairs, format)?);
+    }
+    match errors.pop() {
+        Some(last) => {
+            for err in errors {
+                eprintln!("Error: {}", err);
+            }
+            Err(last)
+        }
+        None => Ok(()),
+    }
+}
+
+async fn add(client: &TokenTide, config: &mut Config, address: &str) -> Result<()> {
+    let outcome = client.add(config, address).await?;
+    if let Some(before) = outcome.replaced {
+        println!(
+            "Deleted {}. Before pair: {}",
+            before.base_token_symbol, before.pair_address
+        );
+    } else {
+        println!("No pair found before.");
+    }
+    println!(
+        "Appended {}. Now pair: {}",
+        outcome.added.base_token_symbol, outcome.added.pair_address
+    );
+    Ok(())
+}
+
+async fn watch(
+    client: &TokenTide,
+    config: &Config,
+    tokens: &[String],
+    interval: Duration,
+) -> Result<()> {
+    let mut watcher = Watcher::resolve(client, config, tokens).await?;
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let rows = watcher.tick(client).await;
+        // Clear the screen and move the cursor home to redraw in place.
+        print!("\x1B[2J\x1B[H");
+        watch::watch_table(&rows).printstd();
+        println!(
+            "Refreshing every {}s, press Ctrl-C to stop.",
+            interval.as_secs()
+        );
+    }
+}
+
+async fn alert(
+    client: &TokenTide,
+    config: &mut Config,
+    matches: &ArgMatches,
+    format: Format,
+) -> Result<()> {
+    match matches.subcommand() {
+        Some(("add", sub_matches)) => {
+            let rule = AlertRule {
+                token: sub_matches
+                    .get_one::<String>("TOKEN")
+                    .expect("required")
+                    .clone(),
+                metric: *sub_matches.get_one::<Metric>("METRIC").expect("required"),
+                condition: *sub_matches
+                    .get_one::<Condition>("CONDITION")
+                    .expect("required"),
+                threshold: *sub_matches.get_one::<f64>("THRESHOLD").expect("required"),
+            };
+            println!("Added alert: {}", rule);
+            config.add_alert(rule)
+        }
+        Some(("list", _)) => {
+            let rules = config.alerts();
+            print!(
+                "{}",
+                output::render_rows(rules, alert::rules_table(rules), format)?
+            );
+            Ok(())
+        }
+        Some(("remove", sub_matches)) => {
+            let index = *sub_matches.get_one::<u64>("INDEX").expect("required") as usize;
+            match config.remove_alert(index - 1)? {
+                Some(rule) => {
+                    println!("Removed alert: {}", rule);
+                    Ok(())
+                }
+                None => Err(TokenTideError::NotFound(format!("alert #{}", index))),
+            }
+        }
+        Some(("check", sub_matches)) => {
+            let all = sub_matches.get_flag("all");
+            let statuses = alert::check(client, config).await;
+            let triggered = statuses.iter().any(|status| status.triggered);
+            // Rules that could not be checked are reported on stderr, and
+            // decide the exit code when nothing triggered.
+            let mut failed = None;
+            for status in &statuses {
+                if let Some(err) = &status.error {
+                    eprintln!("Error: {}: {}", status.rule, err);
+                    failed = Some(err.exit_code());
+                }
+            }
+            let shown: Vec<_> = statuses
+                .into_iter()
+                .filter(|status| all || status.triggered)
+                .collect();
+            if !shown.is_empty() {
+                print!(
+                    "{}",
+                    output::render_rows(&shown, alert::statuses_table(&shown), format)?
+                );
+            }
+            if sub_matches.get_flag("notify") {
+                notify(client, config, &shown).await?;
+            }
+            if triggered {
+                process::exit(alert::TRIGGERED_EXIT_CODE);
+            }
+            if let Some(code) = failed {
+                process::exit(code);
+            }
+            Ok(())
         }
         _ => unreachable!(),
     }
 }
+
+async fn portfolio(
+    client: &TokenTide,
+    config: &mut Config,
+    matches: &ArgMatches,
+    format: Format,
+) -> Result<()> {
+    match matches.subcommand() {
+        Some(("add", sub_matches)) => {
+            let token = sub_matches.get_one::<String>("TOKEN").expect("required");
+            let pair = client.resolve(config, token).await?;
+            let amount = *sub_matches.get_one::<f64>("AMOUNT").expect("required");
+            // Recorded as a buy today, so the amount can be sold and shows in
+            // `portfolio pnl`. Without a cost, it is priced at the current price.
+            let price_usd = match sub_matches.get_one::<f64>("cost") {
+                Some(cost) => cost / amount,
+                None => client
+                    .fetch(&pair)
+                    .await?
+                    .price_usd
+                    .and_then(|price| price.parse().ok())
+                    .ok_or_else(|| TokenTideError::NotFound(format!("price of {}", token)))?,
+            };
+            let key = pair.key();
+            config.record_transaction(Transaction {
+                date: pnl::today(),
+                side: Side::Buy,
+                amount,
+                price_usd,
+                pair,
+            })?;
+            if let Some(holding) = config
+                .holdings()
+                .iter()
+                .find(|holding| holding.pair.key() == key)
+            {
+                println!(
+                    "Holding {} {}",
+                    holding.amount, holding.pair.base_token_symbol
+                );
+            }
+            Ok(())
+        }
+        Some(("remove", sub_matches)) => {
+            let token = sub_matches.get_one::<String>("TOKEN").expect("required");
+            match config.remove_holding(token)? {
+                Some(holding) => {
+                    println!("Removed holding: {}", holding.pair.base_token_symbol);
+                    Ok(())
+                }
+                None => Err(TokenTideError::NotFound(format!("holding of {}", token))),
+            }
+        }
+        Some(("show", _)) => {
+            let positions = portfolio::value(client, config.holdings()).await;
+            print!(
+                "{}",
+                output::render_rows(&positions, portfolio::positions_table(&positions), format)?
+            );
+            // Holdings that could not be priced are reported after the rest.
+            let mut failed = None;
+            for position in &positions {
+                if let Some(err) = &position.error {
+                    eprintln!(
+                        "Error: {}: {}",
+                        position.holding.pair.base_token_symbol, err
+                    );
+                    failed = Some(err.exit_code());
+                }
+            }
+            if let Some(code) = failed {
+                process::exit(code);
+            }
+            Ok(())
+        }
+        Some((side @ ("buy" | "sell"), sub_matches)) => {
+            let token = sub_matches.get_one::<String>("TOKEN").expect("required");
+            let pair = client.resolve(config, token).await?;
+            let price_usd = match sub_matches.get_one::<f64>("price") {
+                Some(price) => *price,
+                None => client
+                    .fetch(&pair)
+                    .await?
+                    .price_usd
+                    .and_then(|price| price.parse().ok())
+                    .ok_or_else(|| TokenTideError::NotFound(format!("price of {}", token)))?,
+            };
+            let transaction = Transaction {
+                date: sub_matches
+                    .get_one::<chrono::NaiveDate>("date")
+                    .copied()
+                    .unwrap_or_else(pnl::today),
+                side: if side == "buy" { Side::Buy } else { Side::Sell },
+                amount: *sub_matches.get_one::<f64>("AMOUNT").expect("required"),
+                price_usd,
+                pair,
+            };
+            let summary = format!(
+                "Recorded {} of {} {} at ${} on {}",
+                transaction.side,
+                transaction.amount,
+                transaction.pair.base_token_symbol,
+                transaction.price_usd,
+                transaction.date
+            );
+            config.record_transaction(transaction)?;
+            println!("{}", summary);
+            Ok(())
+        }
+        Some(("transactions", _)) => {
+            let transactions = config.transactions();
+            print!(
+                "{}",
+                output::render_rows(
+                    &pnl::transaction_rows(transactions),
+                    pnl::transactions_table(transactions),
+                    format
+                )?
+            );
+            Ok(())
+        }
+        Some(("pnl", sub_matches)) => {
+            let method: pnl::Method = sub_matches
+                .get_one::<String>("method")
+                .expect("default")
+                .parse()
+                .expect("validated by clap");
+            let rows = pnl::report(client, config.transactions(), method).await?;
+            print!(
+                "{}",
+                output::render_rows(&rows, pnl::pnl_table(&rows), format)?
+            );
+            Ok(())
+        }
+        Some(("export", sub_matches)) => {
+            let journal: Journal = sub_matches
+                .get_one::<String>("JOURNAL")
+                .expect("required")
+                .parse()
+                .expect("validated by clap");
+            let method: pnl::Method = sub_matches
+                .get_one::<String>("method")
+                .expect("default")
+                .parse()
+                .expect("validated by clap");
+            let traded: Vec<(String, String)> = config
+                .transactions()
+                .iter()
+                .map(|transaction| transaction.pair.key())
+                .collect();
+            let snapshots = History::beside(config_path(config))
+                .read(|snapshot| traded.contains(&snapshot.key()))?;
+            print!(
+                "{}",
+                journal::export(journal, config.transactions(), &snapshots, method)?
+            );
+            Ok(())
+        }
+        _ => unreachable!(),
+    }
+}
+
+async fn notify(client: &TokenTide, config: &Config, statuses: &[AlertStatus]) -> Result<()> {
+    let state_path = notify::state_path(config_path(config));
+    let mut state = NotifyState::load(&state_path)?;
+    let notifier = Notifier::new(config.notifiers().to_vec())
+        .with_client(client.source().http().client().clone());
+    let mut failed = 0;
+    for (name, status, delivery) in notifier.notify(statuses, &mut state).await {
+        match delivery {
+            Delivery::Sent => eprintln!("Notified {}: {}", name, status.rule),
+            Delivery::CoolingDown => {
+                eprintln!("Skipped {}: {} was sent recently", name, status.rule)
+            }
+            Delivery::Failed(err) => {
+                eprintln!("Error: {}: {}", name, err);
+                failed += 1;
+            }
+        }
+    }
+    state.save(&state_path)?;
+    if failed > 0 {
+        return Err(TokenTideError::Notify(format!(
+            "{} notification(s) could not be delivered",
+            failed
+        )));
+    }
+    Ok(())
+}
+
+fn pins(config: &mut Config, matches: &ArgMatches, format: Format) -> Result<()> {
+    let selected = |config: &Config, matches: &ArgMatches| -> Result<PairConfig> {
+        let pin = matches.get_one::<String>("PIN").expect("required");
+        Ok(config.pairs()[pins::select(config.pairs(), pin)?].clone())
+    };
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            let rows = pins::pin_rows(config.pairs());
+            print!(
+                "{}",
+                output::render_rows(&rows, pins::pins_table(&rows), format)?
+            );
+            Ok(())
+        }
+        Some(("remove", sub_matches)) => {
+            let pair = selected(config, sub_matches)?;
+            let question = format!("Unpin {}?", pins::describe(&pair));
+            if !confirm(&question, sub_matches.get_flag("yes"))? {
+                println!("Kept {}", pair.base_token_symbol);
+                return Ok(());
+            }
+            match config.remove_pair(&pair.chain_id, &pair.pair_address)? {
+                Some(removed) => {
+                    println!("Unpinned {}", pins::describe(&removed));
+                    Ok(())
+                }
+                None => Err(TokenTideError::NotFound(format!(
+                    "pin {}",
+                    pair.pair_address
+                ))),
+            }
+        }
+        Some(("clear", sub_matches)) => {
+            let count = config.pairs().len();
+            if count == 0 {
+                println!("No pinned pairs");
+                return Ok(());
+            }
+            let question = format!("Unpin all {} pairs?", count);
+            if !confirm(&question, sub_matches.get_flag("yes"))? {
+                println!("Kept {} pairs", count);
+                return Ok(());
+            }
+            config.clear()?;
+            println!("Unpinned {} pairs", count);
+            Ok(())
+        }
+        Some(("move", sub_matches)) => {
+            let pair = selected(config, sub_matches)?;
+            let position = *sub_matches.get_one::<u64>("POSITION").expect("required") as usize;
+            match config.move_pair(&pair.chain_id, &pair.pair_address, position - 1)? {
+                Some(idx) => {
+                    println!("Moved {} to #{}", pair.base_token_symbol, idx + 1);
+                    Ok(())
+                }
+                None => Err(TokenTideError::NotFound(format!(
+                    "pin {}",
+                    pair.pair_address
+                ))),
+            }
+        }
+        Some(("rename", sub_matches)) => {
+            let pair = selected(config, sub_matches)?;
+            let alias = sub_matches
+                .get_one::<String>("ALIAS")
+                .map(|alias| alias.trim().to_string())
+                .filter(|alias| !alias.is_empty());
+            match config.rename_pair(&pair.chain_id, &pair.pair_address, alias)? {
+                Some(renamed) => {
+                    match &renamed.alias {
+                        Some(alias) => {
+                            println!("{} is now also {}", pins::describe(&renamed), alias)
+                        }
+                        None => println!("Removed the alias of {}", pins::describe(&renamed)),
+                    }
+                    Ok(())
+                }
+                None => Err(TokenTideError::NotFound(format!(
+                    "pin {}",
+                    pair.pair_address
+                ))),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn profile_command(config: &mut Config, matches: &ArgMatches, format: Format) -> Result<()> {
+    let format_arg = |matches: &ArgMatches, id: &str| {
+        matches
+            .get_one::<String>(id)
+            .map(|format| format.parse::<Format>().expect("validated by clap"))
+    };
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            let rows = profile::profile_rows(
+                &config.default_profile(),
+                config.profiles(),
+                config.profile(),
+            );
+            print!(
+                "{}",
+                output::render_rows(&rows, profile::profiles_table(&rows), format)?
+            );
+            Ok(())
+        }
+        Some(("add", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("NAME").expect("required");
+            config.add_profile(name, format_arg(sub_matches, "default-format"))?;
+            println!("Added profile {0}, use it with --profile {0}", name.trim());
+            Ok(())
+        }
+        Some(("format", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("NAME").expect("required");
+            let profile_format = format_arg(sub_matches, "FORMAT");
+            config.set_profile_format(name, profile_format)?;
+            match profile_format {
+                Some(profile_format) => {
+                    println!("Profile {} prints {} by default", name, profile_format)
+                }
+                None => println!("Profile {} prints the default format", name),
+            }
+            Ok(())
+        }
+        Some(("remove", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("NAME").expect("required");
+            let Some(found) = config.profiles().get(name) else {
+                return Err(TokenTideError::NotFound(format!("profile {}", name)));
+            };
+            let question = format!(
+                "Remove profile {} with {} pins and {} alerts?",
+                name,
+                found.pairs.len(),
+                found.alerts.len()
+            );
+            if !confirm(&question, sub_matches.get_flag("yes"))? {
+                println!("Kept profile {}", name);
+                return Ok(());
+            }
+            match config.remove_profile(name)? {
+                Some(_) => {
+                    println!("Removed profile {}", name);
+                    Ok(())
+                }
+                None => Err(TokenTideError::NotFound(format!("profile {}", name))),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Asks a yes or no question on the terminal, `--yes` answering it up front.
+/// Without a terminal to ask on, nothing destructive happens unasked.
+fn confirm(question: &str, yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Err(TokenTideError::Invalid(format!(
+            "{} Pass --yes to confirm without a terminal",
+            question
+        )));
+    }
+    eprint!("{} [y/N] ", question);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    stdin
+        .read_line(&mut answer)
+        .map_err(|err| TokenTideError::Invalid(err.to_string()))?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn config_command(config_path: &Path, matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("doctor", sub_matches)) => {
+            let fix = sub_matches.get_flag("fix");
+            let (diagnosis, backup_path) = if fix {
+                doctor::repair(config_path)?
+            } else {
+                (doctor::check(config_path)?, None)
+            };
+            for problem in &diagnosis.problems {
+                println!("{}", problem);
+            }
+            if diagnosis.problems.is_empty() {
+                println!("No problems found in {}", config_path.display());
+                return Ok(());
+            }
+            match backup_path {
+                Some(backup_path) => {
+                    println!(
+                        "Backed up {} to {}",
+                        config_path.display(),
+                        backup_path.display()
+                    );
+                    println!("Repaired {}", config_path.display());
+                    Ok(())
+                }
+                None => {
+                    eprintln!("Run `tt config doctor --fix` to back the file up and repair it.");
+                    Err(TokenTideError::ConfigInvalid {
+                        path: config_path.to_path_buf(),
+                        problems: diagnosis.problems.len(),
+                    })
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// The config file, next to which the history and notification state live.
+fn config_path(config: &Config) -> &Path {
+    config.file().expect("loaded from a file")
+}
+
+/// Reads the snapshots recorded for a token within the last `since`.
+fn recorded(config: &Config, token: &str, since: Duration) -> Result<Vec<Snapshot>> {
+    let history = History::beside(config_path(config));
+    let since = history::unix_now().saturating_sub(since.as_secs());
+    // Prefer the pinned pair, so a symbol traded in several pools gives one series.
+    let mut snapshots = match config.search_token(token)? {
+        Some(pinned) => history.series(&pinned.chain_id, &pinned.pair_address, since)?,
+        None => vec![],
+    };
+    if snapshots.is_empty() {
+        snapshots =
+            history.read(|snapshot| snapshot.timestamp >= since && snapshot.matches(token))?;
+    }
+    if snapshots.is_empty() {
+        return Err(TokenTideError::NotFound(format!("history of {}", token)));
+    }
+    Ok(snapshots)
+}
+
+fn print_history(config: &Config, token: &str, since: Duration, format: Format) -> Result<()> {
+    let snapshots = recorded(config, token, since)?;
+    print!(
+        "{}",
+        output::render_rows(&snapshots, history::history_table(&snapshots), format)?
+    );
+    Ok(())
+}
+
+fn print_chart(config: &Config, token: &str, since: Duration, options: ChartOptions) -> Result<()> {
+    let snapshots = recorded(config, token, since)?;
+    let last = &snapshots[snapshots.len() - 1];
+    let title = format!(
+        "{}{} price",
+        last.base_token_symbol, last.quote_token_symbol
+    );
+    match chart::render(&title, &snapshots, options) {
+        Some(chart) => {
+            print!("{}", chart);
+            Ok(())
+        }
+        None => Err(TokenTideError::NotFound(format!("prices of {}", token))),
+    }
+}
@@ -0,0 +1,131 @@
+use toml::{Table, Value};
+
+/// Version of the config schema this build reads and writes. Files without a
+/// `version` key are version 1.
+pub const CURRENT_VERSION: i64 = 4;
+
+/// Upgrade steps, the one at index `i` taking version `i + 1` to `i + 2`.
+const MIGRATIONS: [fn(&mut Table, u64); (CURRENT_VERSION - 1) as usize] =
+    [stamp_added_at, lowercase_chain_ids, add_profiles];
+
+/// Upgrades a config table from the given version to [`CURRENT_VERSION`],
+/// `now` being the unix time recorded where a step needs one.
+pub fn migrate(table: &mut Table, from: i64, now: u64) {
+    for step in from.max(1)..CURRENT_VERSION {
+        MIGRATIONS[(step - 1) as usize](table, now);
+    }
+    table.insert("version".to_string(), Value::Integer(CURRENT_VERSION));
+}
+
+/// Calls `f` with every pinned pair: the pinned pairs themselves and the pairs
+/// holdings and transactions are priced by.
+fn for_each_pin(table: &mut Table, mut f: impl FnMut(&mut Table)) {
+    let lists = [("pairs", false), ("holdings", true), ("transactions", true)];
+    for (key, nested) in lists {
+        let Some(Value::Array(entries)) = table.get_mut(key) else {
+            continue;
+        };
+        for entry in entries {
+            let pin = match entry {
+                Value::Table(entry) if nested => entry.get_mut("pair"),
+                entry => Some(entry),
+            };
+            if let Some(Value::Table(pin)) = pin {
+                f(pin);
+            }
+        }
+    }
+}
+
+/// Version 2 records when a pair was pinned. Pairs pinned before that get the
+/// time of the upgrade, the latest they can have been pinned.
+fn stamp_added_at(table: &mut Table, now: u64) {
+    for_each_pin(table, |pin| {
+        pin.entry("added_at")
+            .or_insert(Value::Integer(now.min(i64::MAX as u64) as i64));
+    });
+}
+
+/// Version 3 keeps chain ids lowercase, as the API expects them in URLs.
+fn lowercase_chain_ids(table: &mut Table, _now: u64) {
+    for_each_pin(table, |pin| {
+        if let Some(Value::String(chain_id)) = pin.get_mut("chain_id") {
+            *chain_id = chain_id.to_lowercase();
+        }
+    });
+}
+
+/// Version 4 adds `profiles` and a default `format`. Older files need no
+/// change; the new version keeps builds that do not know the keys, and would
+/// drop them when saving, from loading the file.
+fn add_profiles(_table: &mut Table, _now: u64) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: &str = r#"
+[[pairs]]
+chain_id = "Solana"
+base_token_symbol = "HONEY"
+quote_token_symbol = "USDC"
+base_token_address = "4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy"
+pair_address = "2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo"
+
+[[holdings]]
+amount = 10.0
+
+[holdings.pair]
+chain_id = "SOLANA"
+base_token_symbol = "HONEY"
+quote_token_symbol = "USDC"
+base_token_address = "4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy"
+pair_address = "2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo"
+added_at = 1700000000
+"#;
+
+    fn pins(table: &mut Table) -> Vec<Table> {
+        let mut pins = vec![];
+        for_each_pin(table, |pin| pins.push(pin.clone()));
+        pins
+    }
+
+    #[test]
+    fn test_stamp_added_at() {
+        let mut table: Table = V1.parse().unwrap();
+        stamp_added_at(&mut table, 1800000000);
+        let pins = pins(&mut table);
+        assert_eq!(pins.len(), 2);
+        assert_eq!(pins[0]["added_at"], Value::Integer(1800000000));
+        // A pin that already has a time keeps it.
+        assert_eq!(pins[1]["added_at"], Value::Integer(1700000000));
+    }
+
+    #[test]
+    fn test_lowercase_chain_ids() {
+        let mut table: Table = V1.parse().unwrap();
+        lowercase_chain_ids(&mut table, 0);
+        let chain_ids: Vec<Value> = pins(&mut table)
+            .into_iter()
+            .map(|pin| pin["chain_id"].clone())
+            .collect();
+        assert_eq!(chain_ids, [Value::from("solana"), Value::from("solana")]);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut table: Table = V1.parse().unwrap();
+        migrate(&mut table, 1, 1800000000);
+        assert_eq!(table["version"], Value::Integer(CURRENT_VERSION));
+        let config: crate::Config = Value::Table(table).try_into().unwrap();
+        let pair = &config.pairs()[0];
+        assert_eq!(pair.chain_id, "solana");
+        assert_eq!(pair.added_at, Some(1800000000));
+        assert_eq!(config.holdings()[0].pair.added_at, Some(1700000000));
+
+        // Migrating from the current version only sets the version.
+        let mut table: Table = "pairs = []".parse().unwrap();
+        migrate(&mut table, CURRENT_VERSION, 0);
+        assert_eq!(table.len(), 2);
+    }
+}
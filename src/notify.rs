@@ -0,0 +1,504 @@
+use crate::alert::AlertStatus;
+use crate::config;
+use crate::error::{Result, TokenTideError};
+use crate::history::unix_now;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Where a notifier delivers triggered alerts.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Sink {
+    /// POSTs JSON to a URL. Without a template the payload is the alert status
+    /// as printed by `tt alert check --format json`.
+    Webhook {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+    /// POSTs a Slack incoming-webhook payload (`{"text": ...}`).
+    Slack { url: String },
+    /// POSTs a Discord webhook payload (`{"content": ...}`).
+    Discord { url: String },
+    /// Runs a shell command with the alert in `TT_*` environment variables.
+    Command { command: String },
+}
+
+/// A notifier stored in the config.
+///
+/// ```toml
+/// [[notifiers]]
+/// name = "desk"
+/// kind = "slack"
+/// url = "https://hooks.slack.com/services/..."
+/// cooldown_secs = 3600
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NotifierConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub sink: Sink,
+    /// Extra attempts after a failed delivery.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Minimum seconds between two notifications for the same rule, so a
+    /// flapping price does not spam.
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_cooldown_secs() -> u64 {
+    3600
+}
+
+/// When each rule was last sent to each notifier, kept next to the config.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NotifyState {
+    sent: HashMap<String, u64>,
+}
+
+impl NotifyState {
+    /// Loads the state, starting empty if the file does not exist yet.
+    pub fn load(path: &Path) -> Result<NotifyState> {
+        if !path.exists() {
+            return Ok(NotifyState::default());
+        }
+        let content = fs::read_to_string(path).map_err(|source| TokenTideError::ConfigIo {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        // A damaged state file only means a notification may be repeated.
+        Ok(serde_json::from_str(&content).unwrap_or_default())
+    }
+
+    /// Saves the state under the same lock as the config, keeping the latest
+    /// send time of each rule so concurrent `tt alert check` runs merge.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let _lock = config::lock(path)?;
+        let mut merged = NotifyState::load(path)?;
+        for (key, sent) in &self.sent {
+            let latest = merged.sent.entry(key.clone()).or_default();
+            *latest = (*latest).max(*sent);
+        }
+        let content = serde_json::to_string_pretty(&merged)?;
+        config::write_atomic(path, content.as_bytes()).map_err(|source| TokenTideError::ConfigIo {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn key(notifier: &NotifierConfig, status: &AlertStatus) -> String {
+        format!("{}/{}", notifier.name, status.rule)
+    }
+
+    fn cooling_down(&self, notifier: &NotifierConfig, status: &AlertStatus, now: u64) -> bool {
+        self.sent
+            .get(&NotifyState::key(notifier, status))
+            .is_some_and(|sent| now.saturating_sub(*sent) < notifier.cooldown_secs)
+    }
+
+    fn mark_sent(&mut self, notifier: &NotifierConfig, status: &AlertStatus, now: u64) {
+        self.sent.insert(NotifyState::key(notifier, status), now);
+    }
+}
+
+/// Result of handing one triggered rule to one notifier.
+#[derive(Debug)]
+pub enum Delivery {
+    Sent,
+    /// Skipped because the rule was sent within the cooldown.
+    CoolingDown,
+    Failed(TokenTideError),
+}
+
+/// Delivers triggered alerts to the configured sinks.
+pub struct Notifier {
+    notifiers: Vec<NotifierConfig>,
+    client: reqwest::Client,
+    retry_delay: Duration,
+}
+
+impl Notifier {
+    pub fn new(notifiers: Vec<NotifierConfig>) -> Notifier {
+        Notifier {
+            notifiers,
+            client: reqwest::Client::new(),
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Sends webhooks through the given client, e.g. one configured with the
+    /// proxy of the API requests.
+    pub fn with_client(mut self, client: reqwest::Client) -> Notifier {
+        self.client = client;
+        self
+    }
+
+    /// Sets the delay before the first retry, doubled on every further retry.
+    pub fn with_retry_delay(mut self, retry_delay: Duration) -> Notifier {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Sends every triggered status to every notifier, honoring cooldowns.
+    /// Returns one delivery per notifier and triggered status.
+    pub async fn notify(
+        &self,
+        statuses: &[AlertStatus],
+        state: &mut NotifyState,
+    ) -> Vec<(String, AlertStatus, Delivery)> {
+        let now = unix_now();
+        let mut deliveries = vec![];
+        for status in statuses.iter().filter(|status| status.triggered) {
+            for notifier in &self.notifiers {
+                let delivery = if state.cooling_down(notifier, status, now) {
+                    Delivery::CoolingDown
+                } else {
+                    match self.send(notifier, status).await {
+                        Ok(()) => {
+                            state.mark_sent(notifier, status, now);
+                            Delivery::Sent
+                        }
+                        Err(err) => Delivery::Failed(err),
+                    }
+                };
+                deliveries.push((notifier.name.clone(), status.clone(), delivery));
+            }
+        }
+        deliveries
+    }
+
+    /// Sends one status to one notifier, retrying with exponential backoff.
+    pub async fn send(&self, notifier: &NotifierConfig, status: &AlertStatus) -> Result<()> {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match self.send_once(&notifier.sink, status).await {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt >= notifier.retries => return Err(err),
+                Err(_) => {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    async fn send_once(&self, sink: &Sink, status: &AlertStatus) -> Result<()> {
+        match sink {
+            Sink::Webhook { url, template } => {
+                let body = match template {
+                    Some(template) => render_template(template, status),
+                    None => serde_json::to_string(status)?,
+                };
+                self.post(url, body).await
+            }
+            Sink::Slack { url } => {
+                let body = json!({ "text": message(status) }).to_string();
+                self.post(url, body).await
+            }
+            Sink::Discord { url } => {
+                let body = json!({ "content": message(status) }).to_string();
+                self.post(url, body).await
+            }
+            Sink::Command { command } => run_command(command, status).await,
+        }
+    }
+
+    async fn post(&self, url: &str, body: String) -> Result<()> {
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(TokenTideError::HttpStatus(response.status()))
+        }
+    }
+}
+
+/// Path of the notification state file next to the config file.
+pub fn state_path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name("notify-state.json")
+}
+
+/// One-line description of a triggered alert, used by Slack and Discord.
+pub fn message(status: &AlertStatus) -> String {
+    format!(
+        "Alert triggered: {} ({} is {})",
+        status.rule,
+        status.pair,
+        status
+            .value
+            .map(|value| status.rule.metric.format(value))
+            .unwrap_or_default()
+    )
+}
+
+/// Values an alert exposes to templates (`{{name}}`) and commands (`TT_NAME`).
+fn variables(status: &AlertStatus) -> Vec<(&'static str, String)> {
+    vec![
+        ("rule", status.rule.to_string()),
+        ("token", status.rule.token.clone()),
+        ("metric", status.rule.metric.to_string()),
+        ("condition", status.rule.condition.to_string()),
+        ("threshold", status.rule.threshold.to_string()),
+        ("pair", status.pair.clone()),
+        (
+            "value",
+            status
+                .value
+                .map(|value| value.to_string())
+                .unwrap_or_default(),
+        ),
+        ("message", message(status)),
+    ]
+}
+
+/// Fills `{{name}}` placeholders of a JSON template. Values are escaped so
+/// they can sit inside JSON strings.
+pub fn render_template(template: &str, status: &AlertStatus) -> String {
+    let mut body = template.to_string();
+    for (name, value) in variables(status) {
+        let escaped = serde_json::to_string(&value).unwrap_or_default();
+        let escaped = &escaped[1..escaped.len() - 1];
+        body = body.replace(&format!("{{{{{}}}}}", name), escaped);
+    }
+    body
+}
+
+async fn run_command(command: &str, status: &AlertStatus) -> Result<()> {
+    let mut process = if cfg!(windows) {
+        let mut process = Command::new("cmd");
+        process.arg("/C").arg(command);
+        process
+    } else {
+        let mut process = Command::new("sh");
+        process.arg("-c").arg(command);
+        process
+    };
+    for (name, value) in variables(status) {
+        process.env(format!("TT_{}", name.to_uppercase()), value);
+    }
+    let exit = process
+        .status()
+        .await
+        .map_err(|err| TokenTideError::Notify(format!("Cannot run `{}`: {}", command, err)))?;
+    if exit.success() {
+        Ok(())
+    } else {
+        Err(TokenTideError::Notify(format!(
+            "`{}` exited with {}",
+            command, exit
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::{AlertRule, Condition, Metric};
+    use crate::test_util::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn triggered() -> AlertStatus {
+        AlertStatus {
+            rule: AlertRule {
+                token: "honey".to_string(),
+                metric: Metric::PriceUsd,
+                condition: Condition::Above,
+                threshold: 0.1,
+            },
+            pair: "HONEYUSDC".to_string(),
+            value: Some(0.1735),
+            triggered: true,
+            error: None,
+        }
+    }
+
+    /// Serves one canned status per connection and returns the request bodies.
+    async fn stand_in(statuses: Vec<u16>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut bodies = vec![];
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 4096];
+                loop {
+                    let read = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(head_end) = text.find("\r\n\r\n") {
+                        let length = text[..head_end]
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|value| value.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= head_end + 4 + length {
+                            bodies.push(text[head_end + 4..].to_string());
+                            break;
+                        }
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 {} Stand-in\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    fn notifier(sink: Sink) -> NotifierConfig {
+        NotifierConfig {
+            name: "test".to_string(),
+            sink,
+            retries: 2,
+            cooldown_secs: 3600,
+        }
+    }
+
+    #[test]
+    fn test_notifier_config_toml() {
+        let config: NotifierConfig = toml::from_str(
+            r#"
+            name = "desk"
+            kind = "slack"
+            url = "https://hooks.slack.com/services/T/B/X"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.sink,
+            Sink::Slack {
+                url: "https://hooks.slack.com/services/T/B/X".to_string()
+            }
+        );
+        assert_eq!(config.retries, 2);
+        assert_eq!(config.cooldown_secs, 3600);
+    }
+
+    #[test]
+    fn test_render_template() {
+        let body = render_template(
+            r#"{"text": "{{rule}} on {{pair}}: {{value}}"}"#,
+            &triggered(),
+        );
+        assert_eq!(
+            body,
+            r#"{"text": "HONEY price_usd above 0.1 on HONEYUSDC: 0.1735"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slack_retries_until_success() {
+        let (url, handle) = stand_in(vec![500, 200]).await;
+        let sender = Notifier::new(vec![]).with_retry_delay(Duration::from_millis(1));
+        sender
+            .send(&notifier(Sink::Slack { url }), &triggered())
+            .await
+            .unwrap();
+        let bodies = handle.await.unwrap();
+        assert_eq!(bodies.len(), 2);
+        let payload: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+        assert_eq!(
+            payload["text"],
+            "Alert triggered: HONEY price_usd above 0.1 (HONEYUSDC is $0.1735)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_gives_up_after_retries() {
+        let (url, handle) = stand_in(vec![503, 503, 503]).await;
+        let sender = Notifier::new(vec![]).with_retry_delay(Duration::from_millis(1));
+        let err = sender
+            .send(
+                &notifier(Sink::Webhook {
+                    url,
+                    template: None,
+                }),
+                &triggered(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TokenTideError::HttpStatus(_)));
+        let bodies = handle.await.unwrap();
+        let payload: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(payload["metric"], "price_usd");
+        assert_eq!(payload["triggered"], true);
+    }
+
+    #[tokio::test]
+    async fn test_cooldown_dedupes() {
+        let (url, handle) = stand_in(vec![200]).await;
+        let sender = Notifier::new(vec![notifier(Sink::Discord { url })]);
+        let mut state = NotifyState::default();
+        let statuses = vec![triggered()];
+
+        let deliveries = sender.notify(&statuses, &mut state).await;
+        assert!(matches!(deliveries[0].2, Delivery::Sent));
+        let deliveries = sender.notify(&statuses, &mut state).await;
+        assert!(matches!(deliveries[0].2, Delivery::CoolingDown));
+
+        let bodies = handle.await.unwrap();
+        let payload: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert!(payload["content"].as_str().unwrap().contains("HONEY"));
+    }
+
+    #[test]
+    fn test_save_merges() {
+        let dir = TempDir::new("notify");
+        let path = dir.join("notify-state.json");
+        let mut first = NotifyState::load(&path).unwrap();
+        let mut second = first.clone();
+        first.sent.insert("desk/honey".to_string(), 100);
+        second.sent.insert("desk/honey".to_string(), 50);
+        second.sent.insert("desk/wif".to_string(), 70);
+        first.save(&path).unwrap();
+        second.save(&path).unwrap();
+
+        let saved = NotifyState::load(&path).unwrap();
+        assert_eq!(saved.sent["desk/honey"], 100);
+        assert_eq!(saved.sent["desk/wif"], 70);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_env() {
+        let sender = Notifier::new(vec![]);
+        let ok = notifier(Sink::Command {
+            command: r#"test "$TT_PAIR" = HONEYUSDC && test "$TT_METRIC" = price_usd"#.to_string(),
+        });
+        sender.send(&ok, &triggered()).await.unwrap();
+
+        let mut failing = notifier(Sink::Command {
+            command: "exit 1".to_string(),
+        });
+        failing.retries = 0;
+        let err = sender.send(&failing, &triggered()).await.unwrap_err();
+        assert!(matches!(err, TokenTideError::Notify(_)));
+    }
+}
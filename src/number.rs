@@ -1,3 +1,5 @@
+/// Formats the integer part of a number with thousands separators, e.g.
+/// `1,076,745,043`.
 pub fn to_locale_string(input: f64) -> String {
     let int_part = input.trunc();
     let int_str = int_part.to_string();
@@ -20,6 +22,7 @@ fn insert_commas(integer: &str) -> String {
     rev_int.chars().rev().collect()
 }
 
+/// Formats a number with a K/M/B suffix and two decimals, e.g. `1.08B`.
 pub fn format_with_unit(value: f64) -> String {
     let abs_value = value.abs(); // Work with the absolute value for simplicity
 
@@ -37,3 +40,13 @@ pub fn format_with_unit(value: f64) -> String {
         format!("{:.2}", value)
     }
 }
+
+/// Formats a price, keeping four significant digits below one so small
+/// prices like `0.00001234` stay readable.
+pub fn format_price(value: f64) -> String {
+    if value == 0.0 || value.abs() >= 1.0 {
+        return format_with_unit(value);
+    }
+    let digits = (-value.abs().log10().floor()) as usize + 3;
+    format!("{:.*}", digits, value)
+}
@@ -0,0 +1,235 @@
+use crate::error::{Result, TokenTideError};
+use crate::pair::Pair;
+use crate::table;
+use prettytable::Table;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Output format selected with `--format`, or by the `format` of a config
+/// profile.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Yaml,
+}
+
+impl Format {
+    pub const NAMES: [&'static str; 5] = ["table", "json", "ndjson", "csv", "yaml"];
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            "yaml" => Ok(Format::Yaml),
+            _ => Err(format!("Unknown output format: {}", value)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Table => "table",
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv",
+            Format::Yaml => "yaml",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How many of the pair fields a row carries, mirroring the table columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Detail {
+    /// `tt query --simple`
+    Simple,
+    /// `tt list`
+    List,
+    /// `tt query`
+    Full,
+}
+
+/// The pair fields shown in the tables, flattened for CSV and YAML.
+#[derive(Serialize, Debug)]
+struct PairRow<'a> {
+    pair: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chain: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dex: Option<&'a str>,
+    price_usd: Option<&'a str>,
+    token_address: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume_24h: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fdv: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    liquidity_usd: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pair_address: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+}
+
+impl<'a> PairRow<'a> {
+    fn new(pair: &'a Pair, detail: Detail) -> PairRow<'a> {
+        let listed = detail != Detail::Simple;
+        let full = detail == Detail::Full;
+        PairRow {
+            pair: format!("{}{}", pair.base_token.symbol, pair.quote_token.symbol),
+            chain: listed.then_some(pair.chain_id.as_str()),
+            dex: listed.then_some(pair.dex_id.as_str()),
+            price_usd: pair.price_usd.as_deref(),
+            token_address: &pair.base_token.address,
+            volume_24h: full.then_some(pair.volume.h24),
+            fdv: if full { pair.fdv } else { None },
+            liquidity_usd: if full {
+                pair.liquidity.as_ref().and_then(|value| value.usd)
+            } else {
+                None
+            },
+            pair_address: listed.then_some(pair.pair_address.as_str()),
+            url: full.then_some(pair.url.as_str()),
+        }
+    }
+}
+
+/// Renders the result of `tt query`. JSON formats carry the full pair.
+pub fn render_pair(pair: &Pair, simple: bool, format: Format) -> Result<String> {
+    let detail = if simple { Detail::Simple } else { Detail::Full };
+    match format {
+        Format::Table => Ok(table::pair_table(pair, simple).to_string()),
+        Format::Json => to_json(pair),
+        Format::Ndjson => to_ndjson(std::slice::from_ref(pair)),
+        Format::Csv => to_csv(&[PairRow::new(pair, detail)]),
+        Format::Yaml => to_yaml(&PairRow::new(pair, detail)),
+    }
+}
+
+/// Renders the result of `tt list`. JSON formats carry the full pairs.
+pub fn render_pairs(pairs: &[Pair], format: Format) -> Result<String> {
+    let rows = || -> Vec<PairRow> {
+        pairs
+            .iter()
+            .map(|pair| PairRow::new(pair, Detail::List))
+            .collect()
+    };
+    match format {
+        Format::Table => Ok(table::pairs_table(pairs).to_string()),
+        Format::Json => to_json(&pairs),
+        Format::Ndjson => to_ndjson(pairs),
+        Format::Csv => to_csv(&rows()),
+        Format::Yaml => to_yaml(&rows()),
+    }
+}
+
+/// Renders rows of any serializable record, using `table` for the table
+/// format. CSV needs flat records.
+pub fn render_rows<T: Serialize>(rows: &[T], table: Table, format: Format) -> Result<String> {
+    match format {
+        Format::Table => Ok(table.to_string()),
+        Format::Json => to_json(rows),
+        Format::Ndjson => to_ndjson(rows),
+        Format::Csv => to_csv(rows),
+        Format::Yaml => to_yaml(rows),
+    }
+}
+
+fn output_err(err: impl fmt::Display) -> TokenTideError {
+    TokenTideError::Output(err.to_string())
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value)
+        .map(|json| json + "\n")
+        .map_err(output_err)
+}
+
+fn to_ndjson<T: Serialize>(values: &[T]) -> Result<String> {
+    let mut out = String::new();
+    for value in values {
+        out.push_str(&serde_json::to_string(value).map_err(output_err)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn to_csv<T: Serialize>(rows: &[T]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in rows {
+        writer.serialize(row).map_err(output_err)?;
+    }
+    let bytes = writer.into_inner().map_err(output_err)?;
+    String::from_utf8(bytes).map_err(output_err)
+}
+
+fn to_yaml<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    serde_yaml::to_string(value).map_err(output_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+        assert_eq!("CSV".parse::<Format>().unwrap(), Format::Csv);
+        assert!("xml".parse::<Format>().is_err());
+        for name in Format::NAMES {
+            assert_eq!(name.parse::<Format>().unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_render_pairs_csv() {
+        let csv = render_pairs(&[honey_pair()], Format::Csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("pair,chain,dex,price_usd,token_address,pair_address")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("HONEYUSDC,solana,raydium,0.1735,4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy,2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo")
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn test_render_pair_json_is_full_pair() {
+        let json = render_pair(&honey_pair(), true, Format::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["baseToken"]["symbol"], "HONEY");
+        assert_eq!(value["txns"]["h24"]["buys"], 300);
+    }
+
+    #[test]
+    fn test_render_pair_simple_yaml() {
+        let yaml = render_pair(&honey_pair(), true, Format::Yaml).unwrap();
+        assert_eq!(
+            yaml,
+            "pair: HONEYUSDC\nprice_usd: '0.1735'\ntoken_address: 4vMsoUT2BWatFweudnQM1xedRLfJgJ7hswhcpz4xgBTy\n"
+        );
+    }
+
+    #[test]
+    fn test_render_pairs_ndjson() {
+        let ndjson = render_pairs(&[honey_pair(), honey_pair()], Format::Ndjson).unwrap();
+        assert_eq!(ndjson.lines().count(), 2);
+    }
+}
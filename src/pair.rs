@@ -0,0 +1,107 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A trading pair on a DEX, the model every [`PriceSource`](crate::PriceSource)
+/// maps its responses into.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Pair {
+    pub chain_id: String,
+    pub dex_id: String,
+    pub url: String,
+    pub pair_address: String,
+    pub base_token: Token,
+    pub quote_token: QuoteToken,
+    pub price_native: String,
+    pub price_usd: Option<String>,
+    pub txns: Transactions,
+    pub volume: Volume,
+    pub price_change: PriceChange,
+    pub liquidity: Option<Liquidity>,
+    pub fdv: Option<f64>,
+    pub pair_created_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Token {
+    pub address: String,
+    pub name: String,
+    pub symbol: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuoteToken {
+    pub symbol: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Transactions {
+    pub m5: BuySell,
+    pub h1: BuySell,
+    pub h6: BuySell,
+    pub h24: BuySell,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BuySell {
+    pub buys: i32,
+    pub sells: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Volume {
+    pub m5: f64,
+    pub h1: f64,
+    pub h6: f64,
+    pub h24: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PriceChange {
+    pub m5: f64,
+    pub h1: f64,
+    pub h6: f64,
+    pub h24: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Liquidity {
+    pub usd: Option<f64>,
+    pub base: f64,
+    pub quote: f64,
+}
+
+/// A list of pairs as returned by the pair and search endpoints.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Pairs {
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub pairs: Vec<Pair>,
+}
+
+/// DexScreener answers `"pairs": null` when nothing matches.
+fn null_as_empty<'de, D>(deserializer: D) -> Result<Vec<Pair>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<Vec<Pair>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[cfg(test)]
+pub(crate) fn honey_pair() -> Pair {
+    let pairs: Pairs =
+        serde_json::from_str(include_str!("../tests/fixtures/pairs_honey.json")).unwrap();
+    pairs.pairs[0].clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_null_pairs_are_empty() {
+        let pairs: Pairs =
+            serde_json::from_str(r#"{"schemaVersion": "1.0.0", "pairs": null}"#).unwrap();
+        assert!(pairs.pairs.is_empty());
+        let pairs: Pairs = serde_json::from_str(r#"{"schemaVersion": "1.0.0"}"#).unwrap();
+        assert!(pairs.pairs.is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::config::PairConfig;
+use crate::constants;
+use crate::error::{Result, TokenTideError};
+use crate::history;
+use prettytable::{row, Table};
+use serde::Serialize;
+
+/// A pinned pair as printed by `tt pins list`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PinRow {
+    pub index: usize,
+    pub symbol: String,
+    pub quote: String,
+    pub alias: String,
+    pub chain: String,
+    pub dex: String,
+    pub pair_address: String,
+    pub added: String,
+}
+
+pub fn pin_rows(pairs: &[PairConfig]) -> Vec<PinRow> {
+    pairs
+        .iter()
+        .enumerate()
+        .map(|(idx, pair)| PinRow {
+            index: idx + 1,
+            symbol: pair.base_token_symbol.clone(),
+            quote: pair.quote_token_symbol.clone(),
+            alias: pair.alias.clone().unwrap_or_default(),
+            chain: pair.chain_id.clone(),
+            dex: pair.dex_id.clone().unwrap_or_default(),
+            pair_address: pair.pair_address.clone(),
+            added: pair.added_at.map(history::format_time).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Builds the table printed by `tt pins list`.
+pub fn pins_table(rows: &[PinRow]) -> Table {
+    let or_none = |value: &str| {
+        if value.is_empty() {
+            constants::NONE_STR.to_string()
+        } else {
+            value.to_string()
+        }
+    };
+    let mut table = Table::new();
+    table.add_row(row![
+        "#",
+        "Pair",
+        "Alias",
+        "Chain",
+        "DEX",
+        "Pair Address",
+        "Pinned"
+    ]);
+    for row in rows {
+        table.add_row(row![
+            row.index,
+            format!("{}/{}", row.symbol, row.quote),
+            or_none(&row.alias),
+            row.chain,
+            or_none(&row.dex),
+            row.pair_address,
+            or_none(&row.added)
+        ]);
+    }
+    table
+}
+
+/// Splits a `chain:symbol` search term into the chain and the rest, e.g.
+/// `solana:HONEY`. Terms without a chain prefix come back whole.
+pub fn split_chain(search: &str) -> (Option<&str>, &str) {
+    match search.split_once(':') {
+        Some((chain_id, rest))
+            if !chain_id.is_empty()
+                && !rest.is_empty()
+                && chain_id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+        {
+            (Some(chain_id), rest)
+        }
+        _ => (None, search),
+    }
+}
+
+/// Finds the pins a search term means, in pin order: the pin with that pair
+/// address, else the pins of that token address, else those with that symbol
+/// or alias. A `chain:` prefix only looks at pins on that chain.
+pub fn matching(pairs: &[PairConfig], search: &str) -> Vec<usize> {
+    let (chain_id, term) = split_chain(search);
+    let term = term.to_uppercase();
+    let on_chain: Vec<(usize, &PairConfig)> = pairs
+        .iter()
+        .enumerate()
+        .filter(|(_, pair)| {
+            chain_id.is_none_or(|chain_id| pair.chain_id.eq_ignore_ascii_case(chain_id))
+        })
+        .collect();
+    let by = |field: fn(&PairConfig) -> Vec<String>| -> Vec<usize> {
+        on_chain
+            .iter()
+            .filter(|(_, pair)| field(pair).iter().any(|value| value.to_uppercase() == term))
+            .map(|(idx, _)| *idx)
+            .collect()
+    };
+    [
+        by(|pair| vec![pair.pair_address.clone()]),
+        by(|pair| vec![pair.base_token_address.clone()]),
+        by(|pair| {
+            let mut names = vec![pair.base_token_symbol.clone()];
+            names.extend(pair.alias.clone());
+            names
+        }),
+    ]
+    .into_iter()
+    .find(|found| !found.is_empty())
+    .unwrap_or_default()
+}
+
+/// The error for a search term several pins match, naming them.
+pub fn ambiguous(
+    pairs: &[PairConfig],
+    search: &str,
+    matches: &[usize],
+    hint: &str,
+) -> TokenTideError {
+    TokenTideError::Invalid(format!(
+        "{} matches {} pins ({}), {}",
+        search,
+        matches.len(),
+        matches
+            .iter()
+            .map(|idx| format!("#{} {}", idx + 1, describe(&pairs[*idx])))
+            .collect::<Vec<_>>()
+            .join(", "),
+        hint
+    ))
+}
+
+/// Finds the pin meant by a number shown by `tt pins list`, or a search term
+/// as taken by [`matching`]. Returns its index, or an error naming the
+/// candidates when several pins match.
+pub fn select(pairs: &[PairConfig], pin: &str) -> Result<usize> {
+    if let Ok(number) = pin.parse::<usize>() {
+        if (1..=pairs.len()).contains(&number) {
+            return Ok(number - 1);
+        }
+    }
+    let matches = matching(pairs, pin);
+    match matches.as_slice() {
+        [] => Err(TokenTideError::NotFound(format!("pin {}", pin))),
+        [idx] => Ok(*idx),
+        _ => Err(ambiguous(
+            pairs,
+            pin,
+            &matches,
+            "pick one by number, chain:symbol or pair address",
+        )),
+    }
+}
+
+/// Names a pin in messages, e.g. `HONEY/USDC on solana (2RVV...)`.
+pub fn describe(pair: &PairConfig) -> String {
+    format!(
+        "{}/{} on {} ({})",
+        pair.base_token_symbol, pair.quote_token_symbol, pair.chain_id, pair.pair_address
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+
+    fn pins() -> Vec<PairConfig> {
+        let honey = PairConfig::from(honey_pair());
+        let orca = PairConfig {
+            pair_address: "orca_pair".to_string(),
+            quote_token_symbol: "SOL".to_string(),
+            alias: Some("honey-sol".to_string()),
+            ..honey.clone()
+        };
+        let bonk = PairConfig {
+            base_token_symbol: "Bonk".to_string(),
+            base_token_address: "bonk_address".to_string(),
+            pair_address: "bonk_pair".to_string(),
+            ..honey.clone()
+        };
+        let eth_bonk = PairConfig {
+            chain_id: "ethereum".to_string(),
+            base_token_address: "eth_bonk_address".to_string(),
+            pair_address: "eth_bonk_pair".to_string(),
+            ..bonk.clone()
+        };
+        vec![honey, orca, bonk, eth_bonk]
+    }
+
+    #[test]
+    fn test_select() {
+        let pins = pins();
+        assert_eq!(select(&pins, "3").unwrap(), 2);
+        assert_eq!(select(&pins, "solana:BONK").unwrap(), 2);
+        assert_eq!(select(&pins, "Ethereum:bonk").unwrap(), 3);
+        assert_eq!(select(&pins, "bonk_address").unwrap(), 2);
+        assert_eq!(select(&pins, "ORCA_PAIR").unwrap(), 1);
+        assert_eq!(select(&pins, "honey-sol").unwrap(), 1);
+        match select(&pins, "honey") {
+            Err(TokenTideError::Invalid(message)) => {
+                assert!(message.starts_with("honey matches 2 pins (#1 HONEY/USDC on solana"));
+            }
+            other => panic!("expected ambiguity, got {:?}", other),
+        }
+        assert!(matches!(
+            select(&pins, "bonk"),
+            Err(TokenTideError::Invalid(_))
+        ));
+        assert!(matches!(
+            select(&pins, "5"),
+            Err(TokenTideError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_matching() {
+        let pins = pins();
+        // The quote token does not name a pin.
+        assert!(matching(&pins, "USDC").is_empty());
+        // The pair address wins over the token address it shares.
+        assert_eq!(matching(&pins, "orca_pair"), [1]);
+        assert_eq!(matching(&pins, "BONK"), [2, 3]);
+        assert_eq!(matching(&pins, "solana:bonk"), [2]);
+        assert!(matching(&pins, "base:bonk").is_empty());
+        assert_eq!(split_chain("solana:HONEY"), (Some("solana"), "HONEY"));
+        assert_eq!(split_chain("HONEY"), (None, "HONEY"));
+        assert_eq!(split_chain(":HONEY"), (None, ":HONEY"));
+    }
+
+    #[test]
+    fn test_pin_rows() {
+        let rows = pin_rows(&pins());
+        assert_eq!(rows[1].index, 2);
+        assert_eq!(rows[1].alias, "honey-sol");
+        assert_eq!(rows[0].dex, "raydium");
+        assert!(!rows[0].added.is_empty());
+        let table = pins_table(&rows).to_string();
+        assert!(table.contains("HONEY/SOL"));
+        assert!(table.contains("Bonk/USDC"));
+    }
+}
@@ -0,0 +1,497 @@
+use crate::client::TokenTide;
+use crate::config::PairConfig;
+use crate::error::{Result, TokenTideError};
+use crate::source::PriceSource;
+use crate::{constants, number};
+use chrono::{Local, NaiveDate};
+use futures::future;
+use prettytable::{row, Table};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+/// Amounts below this are treated as zero, to absorb float rounding.
+pub(crate) const DUST: f64 = 1e-9;
+
+/// Whether a transaction bought or sold the token.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Buy => write!(f, "buy"),
+            Side::Sell => write!(f, "sell"),
+        }
+    }
+}
+
+/// A buy or sell recorded in the config, priced in USD per token.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub date: NaiveDate,
+    pub side: Side,
+    pub amount: f64,
+    pub price_usd: f64,
+    /// The pool the token is priced by.
+    pub pair: PairConfig,
+}
+
+/// How sells are matched against earlier buys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Method {
+    /// Sells consume the oldest lots first.
+    #[default]
+    Fifo,
+    /// Sells consume the newest lots first.
+    Lifo,
+    /// Every buy is pooled into one lot at the average cost.
+    Average,
+}
+
+impl Method {
+    pub const NAMES: [&'static str; 3] = ["fifo", "lifo", "average"];
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "fifo" => Ok(Method::Fifo),
+            "lifo" => Ok(Method::Lifo),
+            "average" | "avg" => Ok(Method::Average),
+            _ => Err(format!(
+                "Unknown accounting method: {} (expected one of {})",
+                value,
+                Method::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Fifo => write!(f, "fifo"),
+            Method::Lifo => write!(f, "lifo"),
+            Method::Average => write!(f, "average"),
+        }
+    }
+}
+
+/// Tokens still held from one buy, or from all buys under average cost.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lot {
+    pub date: NaiveDate,
+    pub amount: f64,
+    pub price_usd: f64,
+}
+
+/// Open lots and realized PnL of one token, built by replaying its transactions.
+#[derive(Clone, Debug)]
+pub struct Book {
+    pub pair: PairConfig,
+    method: Method,
+    lots: VecDeque<Lot>,
+    realized: f64,
+}
+
+impl Book {
+    pub fn new(pair: PairConfig, method: Method) -> Book {
+        Book {
+            pair,
+            method,
+            lots: VecDeque::new(),
+            realized: 0.0,
+        }
+    }
+
+    pub fn lots(&self) -> &VecDeque<Lot> {
+        &self.lots
+    }
+
+    /// Amount of the token still held.
+    pub fn amount(&self) -> f64 {
+        // Folded from 0.0, as a float sum of no lots is -0.
+        self.lots.iter().fold(0.0, |sum, lot| sum + lot.amount)
+    }
+
+    /// USD paid for the amount still held.
+    pub fn cost_basis(&self) -> f64 {
+        self.lots
+            .iter()
+            .fold(0.0, |sum, lot| sum + lot.amount * lot.price_usd)
+    }
+
+    /// PnL locked in by sells.
+    pub fn realized(&self) -> f64 {
+        self.realized
+    }
+
+    /// Applies a transaction, returning the parts of lots a sell consumed.
+    /// Selling more than is held is an error.
+    pub fn apply(&mut self, transaction: &Transaction) -> Result<Vec<Lot>> {
+        match transaction.side {
+            Side::Buy => {
+                self.buy(transaction);
+                Ok(vec![])
+            }
+            Side::Sell => self.sell(transaction),
+        }
+    }
+
+    fn buy(&mut self, transaction: &Transaction) {
+        let lot = Lot {
+            date: transaction.date,
+            amount: transaction.amount,
+            price_usd: transaction.price_usd,
+        };
+        match (self.method, self.lots.front_mut()) {
+            (Method::Average, Some(pooled)) => {
+                let cost = pooled.amount * pooled.price_usd + lot.amount * lot.price_usd;
+                pooled.amount += lot.amount;
+                pooled.price_usd = cost / pooled.amount;
+            }
+            _ => self.lots.push_back(lot),
+        }
+    }
+
+    fn sell(&mut self, transaction: &Transaction) -> Result<Vec<Lot>> {
+        let held = self.amount();
+        if transaction.amount > held + DUST {
+            return Err(TokenTideError::Invalid(format!(
+                "cannot sell {} {} on {}, only {} held",
+                transaction.amount, self.pair.base_token_symbol, transaction.date, held
+            )));
+        }
+        let mut consumed = vec![];
+        let mut remaining = transaction.amount;
+        while remaining > DUST {
+            let lot = match self.method {
+                Method::Lifo => self.lots.back_mut(),
+                Method::Fifo | Method::Average => self.lots.front_mut(),
+            };
+            let Some(lot) = lot else { break };
+            let taken = remaining.min(lot.amount);
+            self.realized += taken * (transaction.price_usd - lot.price_usd);
+            consumed.push(Lot {
+                date: lot.date,
+                amount: taken,
+                price_usd: lot.price_usd,
+            });
+            lot.amount -= taken;
+            remaining -= taken;
+            if lot.amount <= DUST {
+                match self.method {
+                    Method::Lifo => self.lots.pop_back(),
+                    Method::Fifo | Method::Average => self.lots.pop_front(),
+                };
+            }
+        }
+        Ok(consumed)
+    }
+}
+
+/// Replays the transactions in date order, one book per pair, in the order
+/// the pairs first appear.
+pub fn books(transactions: &[Transaction], method: Method) -> Result<Vec<Book>> {
+    let mut sorted: Vec<&Transaction> = transactions.iter().collect();
+    sorted.sort_by_key(|transaction| transaction.date);
+    let mut books: Vec<Book> = vec![];
+    for transaction in sorted {
+        let key = transaction.pair.key();
+        let idx = match books.iter().position(|book| book.pair.key() == key) {
+            Some(idx) => idx,
+            None => {
+                books.push(Book::new(transaction.pair.clone(), method));
+                books.len() - 1
+            }
+        };
+        books[idx].apply(transaction)?;
+    }
+    Ok(books)
+}
+
+/// Realized and unrealized PnL of one token.
+#[derive(Serialize, Clone, Debug)]
+pub struct TokenPnl {
+    pub token: String,
+    pub chain: String,
+    pub pair_address: String,
+    pub amount: f64,
+    pub cost_basis: f64,
+    pub price_usd: Option<f64>,
+    pub value_usd: Option<f64>,
+    pub realized: f64,
+    pub unrealized: Option<f64>,
+}
+
+/// Replays the transactions and values the open positions at current prices,
+/// fetched concurrently. Closed positions are not fetched.
+pub async fn report<S: PriceSource>(
+    client: &TokenTide<S>,
+    transactions: &[Transaction],
+    method: Method,
+) -> Result<Vec<TokenPnl>> {
+    let books = books(transactions, method)?;
+    let prices = future::try_join_all(books.iter().map(|book| async move {
+        if book.amount() <= DUST {
+            return Ok(None);
+        }
+        let pair = client.fetch(&book.pair).await?;
+        Ok::<_, TokenTideError>(
+            pair.price_usd
+                .as_deref()
+                .and_then(|price| price.parse().ok()),
+        )
+    }))
+    .await?;
+    Ok(books
+        .iter()
+        .zip(prices)
+        .map(|(book, price_usd)| {
+            let amount = book.amount();
+            let cost_basis = book.cost_basis();
+            let value_usd = if amount <= DUST {
+                Some(0.0)
+            } else {
+                price_usd.map(|price| price * amount)
+            };
+            TokenPnl {
+                token: book.pair.base_token_symbol.clone(),
+                chain: book.pair.chain_id.clone(),
+                pair_address: book.pair.pair_address.clone(),
+                amount,
+                cost_basis,
+                price_usd,
+                value_usd,
+                realized: book.realized(),
+                unrealized: value_usd.map(|value| value - cost_basis),
+            }
+        })
+        .collect())
+}
+
+/// Parses a `YYYY-MM-DD` date.
+pub fn parse_date(value: &str) -> std::result::Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {} (expected YYYY-MM-DD)", value))
+}
+
+/// Parses a USD price per token, which must be a finite number above zero.
+pub fn parse_price(value: &str) -> std::result::Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(price) if price.is_finite() && price > 0.0 => Ok(price),
+        _ => Err(format!(
+            "Invalid price: {} (expected a number above 0)",
+            value
+        )),
+    }
+}
+
+/// Today's date in local time.
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// A transaction flattened for machine-readable output.
+#[derive(Serialize)]
+pub struct TransactionRow<'a> {
+    pub date: NaiveDate,
+    pub side: Side,
+    pub token: &'a str,
+    pub chain: &'a str,
+    pub pair_address: &'a str,
+    pub amount: f64,
+    pub price_usd: f64,
+}
+
+pub fn transaction_rows(transactions: &[Transaction]) -> Vec<TransactionRow<'_>> {
+    transactions
+        .iter()
+        .map(|transaction| TransactionRow {
+            date: transaction.date,
+            side: transaction.side,
+            token: &transaction.pair.base_token_symbol,
+            chain: &transaction.pair.chain_id,
+            pair_address: &transaction.pair.pair_address,
+            amount: transaction.amount,
+            price_usd: transaction.price_usd,
+        })
+        .collect()
+}
+
+fn usd(value: f64) -> String {
+    format!("${}", number::format_with_unit(value))
+}
+
+fn signed_usd(value: f64) -> String {
+    let sign = if value < 0.0 { "-" } else { "+" };
+    format!("{}${}", sign, number::format_with_unit(value.abs()))
+}
+
+/// Builds the table printed by `tt portfolio transactions`.
+pub fn transactions_table(transactions: &[Transaction]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![
+        "#",
+        "Date",
+        "Side",
+        "Token",
+        "Amount",
+        "Price In USD",
+        "Total"
+    ]);
+    for (idx, transaction) in transactions.iter().enumerate() {
+        table.add_row(row![
+            idx + 1,
+            transaction.date,
+            transaction.side,
+            transaction.pair.base_token_symbol,
+            number::format_with_unit(transaction.amount),
+            number::format_price(transaction.price_usd),
+            usd(transaction.amount * transaction.price_usd)
+        ]);
+    }
+    table
+}
+
+/// Builds the table printed by `tt portfolio pnl`, with a total row.
+pub fn pnl_table(rows: &[TokenPnl]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Token",
+        "Amount",
+        "Cost Basis",
+        "Price In USD",
+        "Value",
+        "Realized",
+        "Unrealized"
+    ]);
+    let none = || constants::NONE_STR.to_string();
+    for pnl in rows {
+        table.add_row(row![
+            pnl.token,
+            number::format_with_unit(pnl.amount),
+            usd(pnl.cost_basis),
+            pnl.price_usd.map(number::format_price).unwrap_or_else(none),
+            pnl.value_usd.map(usd).unwrap_or_else(none),
+            signed_usd(pnl.realized),
+            pnl.unrealized.map(signed_usd).unwrap_or_else(none)
+        ]);
+    }
+    let value: Option<f64> = rows.iter().map(|pnl| pnl.value_usd).sum();
+    let unrealized: Option<f64> = rows.iter().map(|pnl| pnl.unrealized).sum();
+    table.add_row(row![
+        "Total",
+        "",
+        usd(rows.iter().map(|pnl| pnl.cost_basis).sum()),
+        "",
+        value.map(usd).unwrap_or_else(none),
+        signed_usd(rows.iter().map(|pnl| pnl.realized).sum()),
+        unrealized.map(signed_usd).unwrap_or_else(none)
+    ]);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+    use crate::source::MockSource;
+
+    fn transaction(date: &str, side: Side, amount: f64, price_usd: f64) -> Transaction {
+        Transaction {
+            date: parse_date(date).unwrap(),
+            side,
+            amount,
+            price_usd,
+            pair: PairConfig::from(honey_pair()),
+        }
+    }
+
+    fn trades() -> Vec<Transaction> {
+        vec![
+            transaction("2024-01-01", Side::Buy, 100.0, 1.0),
+            transaction("2024-02-01", Side::Buy, 100.0, 2.0),
+            transaction("2024-03-01", Side::Sell, 150.0, 3.0),
+        ]
+    }
+
+    #[test]
+    fn test_methods() {
+        let fifo = &books(&trades(), Method::Fifo).unwrap()[0];
+        assert_eq!(fifo.realized(), 100.0 * 2.0 + 50.0 * 1.0);
+        assert_eq!(fifo.amount(), 50.0);
+        assert_eq!(fifo.cost_basis(), 100.0);
+
+        let lifo = &books(&trades(), Method::Lifo).unwrap()[0];
+        assert_eq!(lifo.realized(), 100.0 * 1.0 + 50.0 * 2.0);
+        assert_eq!(lifo.cost_basis(), 50.0);
+
+        let average = &books(&trades(), Method::Average).unwrap()[0];
+        assert_eq!(average.realized(), 150.0 * 1.5);
+        assert_eq!(average.lots().len(), 1);
+        assert_eq!(average.cost_basis(), 75.0);
+    }
+
+    #[test]
+    fn test_same_address_on_two_chains() {
+        let mut other_chain = transaction("2024-01-02", Side::Buy, 10.0, 5.0);
+        other_chain.pair.chain_id = "ethereum".to_string();
+        let mut trades = trades();
+        trades.push(other_chain);
+        let books = books(&trades, Method::Fifo).unwrap();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[1].amount(), 10.0);
+    }
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(parse_price("0.1735"), Ok(0.1735));
+        for value in ["0", "-1", "nan", "inf", "$5"] {
+            assert!(parse_price(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_oversell_and_date_order() {
+        let mut trades = trades();
+        trades.push(transaction("2024-04-01", Side::Sell, 51.0, 3.0));
+        assert!(matches!(
+            books(&trades, Method::Fifo),
+            Err(TokenTideError::Invalid(_))
+        ));
+        let err = books(
+            &[transaction("2024-01-01", Side::Sell, 10.0, 1.0)],
+            Method::Fifo,
+        )
+        .unwrap_err();
+        assert!(err.to_string().ends_with("only 0 held"), "{}", err);
+
+        // A backdated buy is replayed before the sell.
+        let mut trades = trades[..3].to_vec();
+        trades.push(transaction("2023-12-01", Side::Buy, 50.0, 0.5));
+        let fifo = &books(&trades, Method::Fifo).unwrap()[0];
+        assert_eq!(fifo.realized(), 50.0 * 2.5 + 100.0 * 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![honey_pair()],
+        });
+        let rows = report(&client, &trades(), Method::Fifo).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].token, "HONEY");
+        assert_eq!(rows[0].price_usd, Some(0.1735));
+        assert_eq!(rows[0].value_usd, Some(50.0 * 0.1735));
+        assert_eq!(rows[0].unrealized, Some(50.0 * 0.1735 - 100.0));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::client::TokenTide;
+use crate::config::PairConfig;
+use crate::error::TokenTideError;
+use crate::source::PriceSource;
+use crate::{constants, number};
+use futures::future;
+use prettytable::{row, Table};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::sync::Arc;
+
+/// An amount of a token held, priced by the pinned pool.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Holding {
+    pub amount: f64,
+    /// Total USD paid for the amount, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_basis: Option<f64>,
+    /// The pool the holding is priced by.
+    pub pair: PairConfig,
+}
+
+/// Parses an amount of a token, which must be a finite number above zero.
+pub fn parse_amount(value: &str) -> std::result::Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(amount) if amount.is_finite() && amount > 0.0 => Ok(amount),
+        _ => Err(format!(
+            "Invalid amount: {} (expected a number above 0)",
+            value
+        )),
+    }
+}
+
+/// Parses a USD cost, which must be a finite number of at least zero.
+pub fn parse_cost(value: &str) -> std::result::Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(cost) if cost.is_finite() && cost >= 0.0 => Ok(cost),
+        _ => Err(format!(
+            "Invalid cost: {} (expected a number of 0 or more)",
+            value
+        )),
+    }
+}
+
+/// A holding valued at the current price.
+#[derive(Clone, Debug)]
+pub struct Position {
+    pub holding: Holding,
+    pub price_usd: Option<f64>,
+    pub value_usd: f64,
+    /// Share of the total portfolio value, from 0 to 1.
+    pub share: f64,
+    /// Why the price could not be fetched, leaving the position unvalued.
+    pub error: Option<Arc<TokenTideError>>,
+}
+
+// Serialized flat, so CSV output gets one column per field.
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Position", 9)?;
+        state.serialize_field("token", &self.holding.pair.base_token_symbol)?;
+        state.serialize_field("chain", &self.holding.pair.chain_id)?;
+        state.serialize_field("pair_address", &self.holding.pair.pair_address)?;
+        state.serialize_field("amount", &self.holding.amount)?;
+        state.serialize_field("price_usd", &self.price_usd)?;
+        state.serialize_field("value_usd", &self.value_usd)?;
+        state.serialize_field("share", &self.share)?;
+        state.serialize_field("cost_basis", &self.holding.cost_basis)?;
+        state.serialize_field("error", &self.error.as_ref().map(|error| error.to_string()))?;
+        state.end()
+    }
+}
+
+/// Fetches the current price of every holding concurrently and values them.
+/// A holding whose pair cannot be fetched carries the error and is valued at
+/// zero, and the rest are still totaled.
+pub async fn value<S: PriceSource>(client: &TokenTide<S>, holdings: &[Holding]) -> Vec<Position> {
+    let pairs = future::join_all(holdings.iter().map(|holding| client.fetch(&holding.pair))).await;
+    let mut positions: Vec<Position> = holdings
+        .iter()
+        .zip(pairs)
+        .map(|(holding, pair)| {
+            let (price_usd, error) = match pair {
+                Ok(pair) => (
+                    pair.price_usd
+                        .as_deref()
+                        .and_then(|price| price.parse().ok()),
+                    None,
+                ),
+                Err(err) => (None, Some(Arc::new(err))),
+            };
+            Position {
+                holding: holding.clone(),
+                price_usd,
+                value_usd: price_usd.unwrap_or(0.0) * holding.amount,
+                share: 0.0,
+                error,
+            }
+        })
+        .collect();
+    let total = total_value(&positions);
+    if total > 0.0 {
+        for position in positions.iter_mut() {
+            position.share = position.value_usd / total;
+        }
+    }
+    positions
+}
+
+pub fn total_value(positions: &[Position]) -> f64 {
+    positions.iter().map(|position| position.value_usd).sum()
+}
+
+/// Builds the table printed by `tt portfolio show`, with a total row.
+pub fn positions_table(positions: &[Position]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Token",
+        "Chain",
+        "Amount",
+        "Price In USD",
+        "Value",
+        "Share",
+        "Cost Basis"
+    ]);
+    for position in positions {
+        let holding = &position.holding;
+        table.add_row(row![
+            holding.pair.base_token_symbol,
+            holding.pair.chain_id,
+            number::format_with_unit(holding.amount),
+            match (&position.error, position.price_usd) {
+                (Some(_), _) => "error".to_string(),
+                (None, Some(price)) => number::format_price(price),
+                (None, None) => constants::NONE_STR.to_string(),
+            },
+            format!("${}", number::format_with_unit(position.value_usd)),
+            format!("{:.2}%", position.share * 100.0),
+            holding
+                .cost_basis
+                .map(|cost| format!("${}", number::format_with_unit(cost)))
+                .unwrap_or(constants::NONE_STR.to_string())
+        ]);
+    }
+    let cost: Option<f64> = positions
+        .iter()
+        .map(|position| position.holding.cost_basis)
+        .sum();
+    table.add_row(row![
+        "Total",
+        "",
+        "",
+        "",
+        format!("${}", number::format_with_unit(total_value(positions))),
+        if positions.is_empty() { "" } else { "100.00%" },
+        cost.map(|cost| format!("${}", number::format_with_unit(cost)))
+            .unwrap_or_default()
+    ]);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+    use crate::source::MockSource;
+
+    #[test]
+    fn test_parse_amount_and_cost() {
+        assert_eq!(parse_amount("1000"), Ok(1000.0));
+        assert_eq!(parse_amount(" 0.5 "), Ok(0.5));
+        for value in ["0", "-5", "nan", "inf", "lots"] {
+            assert!(parse_amount(value).is_err(), "{}", value);
+        }
+        assert_eq!(parse_cost("0"), Ok(0.0));
+        assert_eq!(parse_cost("250.5"), Ok(250.5));
+        for value in ["-5", "NaN", "-inf", ""] {
+            assert!(parse_cost(value).is_err(), "{}", value);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_value() {
+        let pair = honey_pair();
+        let mut other = pair.clone();
+        other.pair_address = "other".to_string();
+        other.price_usd = Some("2".to_string());
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![pair.clone(), other.clone()],
+        });
+        let holdings = vec![
+            Holding {
+                amount: 1000.0,
+                cost_basis: Some(150.0),
+                pair: PairConfig::from(pair),
+            },
+            Holding {
+                amount: 100.0,
+                cost_basis: None,
+                pair: PairConfig::from(other),
+            },
+        ];
+        let positions = value(&client, &holdings).await;
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].value_usd, 173.5);
+        assert_eq!(positions[1].value_usd, 200.0);
+        assert_eq!(total_value(&positions), 373.5);
+        assert!((positions[0].share + positions[1].share - 1.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_value_past_errors() {
+        let pair = honey_pair();
+        let mut gone = PairConfig::from(pair.clone());
+        gone.pair_address = "gone".to_string();
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![pair.clone()],
+        });
+        let holdings = vec![
+            Holding {
+                amount: 100.0,
+                cost_basis: None,
+                pair: gone,
+            },
+            Holding {
+                amount: 1000.0,
+                cost_basis: None,
+                pair: PairConfig::from(pair),
+            },
+        ];
+        let positions = value(&client, &holdings).await;
+        assert!(matches!(
+            positions[0].error.as_deref(),
+            Some(TokenTideError::NotFound(_))
+        ));
+        assert_eq!(positions[0].price_usd, None);
+        assert_eq!(positions[1].value_usd, 173.5);
+        assert_eq!(total_value(&positions), 173.5);
+        assert_eq!(positions[1].share, 1.0);
+        assert!(positions_table(&positions).to_string().contains("error"));
+    }
+
+    #[test]
+    fn test_holding_toml() {
+        let holding = Holding {
+            amount: 1000.0,
+            cost_basis: None,
+            pair: PairConfig::from(honey_pair()),
+        };
+        let text = toml::to_string(&holding).unwrap();
+        assert!(!text.contains("cost_basis"));
+        assert_eq!(toml::from_str::<Holding>(&text).unwrap(), holding);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::alert::AlertRule;
+use crate::config::PairConfig;
+use crate::constants;
+use crate::error::{Result, TokenTideError};
+use crate::output::Format;
+use prettytable::{row, Table};
+use serde::{Deserialize, Serialize};
+
+/// Environment variable naming the profile to use, like `--profile`.
+pub const PROFILE_ENV: &str = "TOKEN_TIDE_PROFILE";
+
+/// The name the top-level lists of the config go by.
+pub const DEFAULT_NAME: &str = "default";
+
+/// A named watchlist kept in the config under `[profiles.<name>]`, with its
+/// own pinned pairs, alert rules and output format. Commands use it in place
+/// of the top-level lists when picked with `--profile`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    /// The output format used when `--format` is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+    #[serde(default)]
+    pub pairs: Vec<PairConfig>,
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+}
+
+/// Checks the name of a new profile, returning it trimmed.
+pub fn validate_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name == DEFAULT_NAME {
+        return Err(TokenTideError::Invalid(format!(
+            "`{}` names the top-level lists of the config",
+            DEFAULT_NAME
+        )));
+    }
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(TokenTideError::Invalid(format!(
+            "profile name `{}` must be a single word",
+            name
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// A profile as printed by `tt profile list`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ProfileRow {
+    pub name: String,
+    pub active: bool,
+    pub format: String,
+    pub pairs: usize,
+    pub alerts: usize,
+}
+
+/// Lists the top-level lists as `default`, followed by the named profiles.
+pub fn profile_rows<'a>(
+    default: &Profile,
+    profiles: impl IntoIterator<Item = (&'a String, &'a Profile)>,
+    active: Option<&str>,
+) -> Vec<ProfileRow> {
+    let row = |name: &str, profile: &Profile, active: bool| ProfileRow {
+        name: name.to_string(),
+        active,
+        format: profile
+            .format
+            .map(|format| format.to_string())
+            .unwrap_or_default(),
+        pairs: profile.pairs.len(),
+        alerts: profile.alerts.len(),
+    };
+    let mut rows = vec![row(DEFAULT_NAME, default, active.is_none())];
+    for (name, profile) in profiles {
+        rows.push(row(name, profile, active == Some(name.as_str())));
+    }
+    rows
+}
+
+/// Builds the table printed by `tt profile list`, marking the active profile.
+pub fn profiles_table(rows: &[ProfileRow]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["", "Profile", "Format", "Pairs", "Alerts"]);
+    for row in rows {
+        table.add_row(row![
+            if row.active { "*" } else { "" },
+            row.name,
+            if row.format.is_empty() {
+                constants::NONE_STR
+            } else {
+                &row.format
+            },
+            row.pairs,
+            row.alerts
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_profile_rows() {
+        let default = Profile {
+            format: None,
+            pairs: vec![PairConfig::from(honey_pair())],
+            alerts: vec![],
+        };
+        let mut profiles = BTreeMap::new();
+        profiles.insert(
+            "trading".to_string(),
+            Profile {
+                format: Some(Format::Json),
+                ..Profile::default()
+            },
+        );
+        let rows = profile_rows(&default, &profiles, Some("trading"));
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].name, "default");
+        assert_eq!(rows[0].pairs, 1);
+        assert!(!rows[0].active);
+        assert!(rows[1].active);
+        assert_eq!(rows[1].format, "json");
+        let table = profiles_table(&rows).to_string();
+        assert!(table.contains("trading"));
+
+        assert_eq!(validate_name(" trading ").unwrap(), "trading");
+        assert!(validate_name("day trading").is_err());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("default").is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use crate::error::Result;
+use crate::pair::Pair;
+use async_trait::async_trait;
+
+/// A backend that can look up pairs and prices. Every backend maps its
+/// responses into the shared [`Pair`] model.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Searches pairs matching a symbol, name or address.
+    async fn search(&self, query: &str) -> Result<Vec<Pair>>;
+
+    /// Fetches a single pair by chain and pair address.
+    async fn fetch_pair(&self, chain_id: &str, pair_address: &str) -> Result<Vec<Pair>>;
+
+    /// Fetches every pair that trades the given token address.
+    async fn fetch_token_pairs(&self, token_address: &str) -> Result<Vec<Pair>>;
+
+    /// Whether the data returned for a pair came from a cache an earlier run
+    /// filled, rather than from the network, so it is not new. Sources
+    /// without a cache always fetch.
+    fn served_from_cache(&self, _pair: &Pair) -> bool {
+        false
+    }
+
+    /// Fetches several pairs on one chain. Sources with a batch endpoint
+    /// override this, the default fetches the pairs one at a time.
+    async fn fetch_pairs(&self, chain_id: &str, pair_addresses: &[String]) -> Result<Vec<Pair>> {
+        let mut pairs = vec![];
+        for pair_address in pair_addresses {
+            pairs.extend(self.fetch_pair(chain_id, pair_address).await?);
+        }
+        Ok(pairs)
+    }
+}
+
+/// In-memory source serving a fixed set of pairs.
+#[cfg(test)]
+pub(crate) struct MockSource {
+    pub(crate) pairs: Vec<Pair>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl PriceSource for MockSource {
+    async fn search(&self, _query: &str) -> Result<Vec<Pair>> {
+        Ok(self.pairs.clone())
+    }
+
+    async fn fetch_pair(&self, _chain_id: &str, pair_address: &str) -> Result<Vec<Pair>> {
+        Ok(self
+            .pairs
+            .iter()
+            .filter(|pair| pair.pair_address == pair_address)
+            .cloned()
+            .collect())
+    }
+
+    async fn fetch_token_pairs(&self, token_address: &str) -> Result<Vec<Pair>> {
+        Ok(self
+            .pairs
+            .iter()
+            .filter(|pair| pair.base_token.address == token_address)
+            .cloned()
+            .collect())
+    }
+}
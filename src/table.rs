@@ -0,0 +1,104 @@
+use crate::pair::Pair;
+use crate::{constants, number};
+use prettytable::{row, Cell, Row, Table};
+
+/// The property/value rows shown by `tt query`, the simple form only carrying
+/// the pair, its price and the token address.
+pub fn pair_properties(pair: &Pair, simple: bool) -> Vec<(&'static str, String)> {
+    let mut properties = vec![
+        (
+            "Pair",
+            format!("{}{}", pair.base_token.symbol, pair.quote_token.symbol),
+        ),
+        (
+            "Price In USD",
+            pair.price_usd
+                .clone()
+                .unwrap_or(constants::NONE_STR.to_string()),
+        ),
+        ("Token Address", pair.base_token.address.clone()),
+    ];
+
+    if !simple {
+        properties.push(("Chain", pair.chain_id.clone()));
+        properties.push(("DEX", pair.dex_id.clone()));
+        properties.push((
+            "24h Volume",
+            format!(
+                "{} ({})",
+                number::format_with_unit(pair.volume.h24),
+                number::to_locale_string(pair.volume.h24)
+            ),
+        ));
+        properties.push((
+            "FDV",
+            pair.fdv
+                .map(|value| {
+                    format!(
+                        "{} ({})",
+                        number::format_with_unit(value),
+                        number::to_locale_string(value)
+                    )
+                })
+                .unwrap_or(constants::NONE_STR.to_string()),
+        ));
+        properties.push((
+            "Liquidity",
+            pair.liquidity
+                .clone()
+                .map(|value| {
+                    format!(
+                        "${} (${})",
+                        number::format_with_unit(value.usd.unwrap_or(0.0)),
+                        number::to_locale_string(value.usd.unwrap_or(0.0))
+                    )
+                })
+                .unwrap_or(constants::NONE_STR.to_string()),
+        ));
+        properties.push(("Pair Address", pair.pair_address.clone()));
+        properties.push(("Link", pair.url.clone()));
+    }
+    properties
+}
+
+/// Builds the property/value table printed by `tt query`.
+pub fn pair_table(pair: &Pair, simple: bool) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["Property", "Value"]);
+    for (property, value) in pair_properties(pair, simple) {
+        table.add_row(row![property, value]);
+    }
+    table
+}
+
+/// Builds the one-row-per-pair table printed by `tt list`.
+pub fn pairs_table(pairs: &[Pair]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Pair",
+        "Chain",
+        "DEX",
+        "Price In USD",
+        "Token Address",
+        "Pair Address",
+    ]);
+    for pair in pairs {
+        table.add_row(Row::new(vec![
+            Cell::new(&format!(
+                "{}{}",
+                pair.base_token.symbol, pair.quote_token.symbol
+            )),
+            Cell::new(&pair.chain_id),
+            Cell::new(&pair.dex_id),
+            Cell::new(
+                &pair
+                    .price_usd
+                    .clone()
+                    .unwrap_or(constants::NONE_STR.to_string()),
+            ),
+            Cell::new(&pair.base_token.address),
+            Cell::new(&pair.pair_address),
+        ]));
+    }
+    table
+}
@@ -0,0 +1,258 @@
+use crate::client::TokenTide;
+use crate::config::{Config, PairConfig};
+use crate::error::{Result, TokenTideError};
+use crate::pair::Pair;
+use crate::source::PriceSource;
+use crate::{constants, number};
+use prettytable::{row, Cell, Row, Table};
+
+/// Direction of the price since the previous tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Movement {
+    /// First tick, nothing to compare with yet.
+    New,
+    Up(f64),
+    Down(f64),
+    Unchanged,
+}
+
+impl Movement {
+    fn between(previous: Option<f64>, current: Option<f64>) -> Movement {
+        match (previous, current) {
+            (Some(previous), Some(current)) if previous > 0.0 => {
+                let change = (current - previous) / previous * 100.0;
+                if current > previous {
+                    Movement::Up(change)
+                } else if current < previous {
+                    Movement::Down(change)
+                } else {
+                    Movement::Unchanged
+                }
+            }
+            _ => Movement::New,
+        }
+    }
+}
+
+/// A watched pair on one tick: the fetched pair together with its move since
+/// the previous tick, or why it could not be fetched.
+#[derive(Clone, Debug)]
+pub struct WatchRow {
+    pub pinned: PairConfig,
+    pub pair: Option<Pair>,
+    pub movement: Movement,
+    pub error: Option<String>,
+}
+
+/// Polls a fixed set of pairs and tracks how their prices move between ticks.
+pub struct Watcher {
+    pairs: Vec<PairConfig>,
+    previous: Vec<Option<f64>>,
+}
+
+impl Watcher {
+    /// Resolves the tokens to watch, preferring the pairs pinned in the config.
+    /// Without tokens every pinned pair is watched, and having none pinned is
+    /// reported as [`TokenTideError::NotFound`].
+    pub async fn resolve<S: PriceSource>(
+        client: &TokenTide<S>,
+        config: &Config,
+        tokens: &[String],
+    ) -> Result<Watcher> {
+        let pairs = if tokens.is_empty() {
+            config.pairs().to_vec()
+        } else {
+            let mut pairs = vec![];
+            for token in tokens {
+                pairs.push(client.resolve(config, token).await?);
+            }
+            pairs
+        };
+        if pairs.is_empty() {
+            return Err(TokenTideError::NotFound("pinned pairs".to_string()));
+        }
+        Ok(Watcher::new(pairs))
+    }
+
+    pub fn new(pairs: Vec<PairConfig>) -> Watcher {
+        let previous = vec![None; pairs.len()];
+        Watcher { pairs, previous }
+    }
+
+    pub fn pairs(&self) -> &[PairConfig] {
+        &self.pairs
+    }
+
+    /// Fetches every watched pair once. A pair that fails keeps the price of
+    /// the last tick it was fetched on, so its next move is measured from a
+    /// price that was shown.
+    pub async fn tick<S: PriceSource>(&mut self, client: &TokenTide<S>) -> Vec<WatchRow> {
+        let mut rows = vec![];
+        for (idx, pinned) in self.pairs.iter().enumerate() {
+            let row = match client.fetch(pinned).await {
+                Ok(pair) => {
+                    let price = price_usd(&pair);
+                    let movement = Movement::between(self.previous[idx], price);
+                    self.previous[idx] = price;
+                    WatchRow {
+                        pinned: pinned.clone(),
+                        pair: Some(pair),
+                        movement,
+                        error: None,
+                    }
+                }
+                Err(err) => WatchRow {
+                    pinned: pinned.clone(),
+                    pair: None,
+                    movement: Movement::New,
+                    error: Some(err.to_string()),
+                },
+            };
+            rows.push(row);
+        }
+        rows
+    }
+}
+
+fn price_usd(pair: &Pair) -> Option<f64> {
+    pair.price_usd
+        .as_deref()
+        .and_then(|price| price.parse().ok())
+}
+
+fn change_cell(change: f64) -> Cell {
+    let cell = Cell::new(&format!("{:+.2}%", change));
+    if change > 0.0 {
+        cell.style_spec("Fg")
+    } else if change < 0.0 {
+        cell.style_spec("Fr")
+    } else {
+        cell
+    }
+}
+
+/// Builds the table redrawn on every tick of `tt watch`.
+pub fn watch_table(rows: &[WatchRow]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Pair",
+        "Chain",
+        "Price In USD",
+        "Tick",
+        "5m",
+        "1h",
+        "24h Volume"
+    ]);
+    for row in rows {
+        let Some(pair) = &row.pair else {
+            table.add_row(Row::new(vec![
+                Cell::new(&format!(
+                    "{}{}",
+                    row.pinned.base_token_symbol, row.pinned.quote_token_symbol
+                )),
+                Cell::new(&row.pinned.chain_id),
+                Cell::new(&format!(
+                    "Error: {}",
+                    row.error.as_deref().unwrap_or_default()
+                ))
+                .style_spec("Fr"),
+            ]));
+            continue;
+        };
+        let price = pair
+            .price_usd
+            .clone()
+            .unwrap_or(constants::NONE_STR.to_string());
+        let (price_cell, tick_cell) = match row.movement {
+            Movement::Up(change) => (
+                Cell::new(&format!("▲ {}", price)).style_spec("Fg"),
+                change_cell(change),
+            ),
+            Movement::Down(change) => (
+                Cell::new(&format!("▼ {}", price)).style_spec("Fr"),
+                change_cell(change),
+            ),
+            Movement::Unchanged => (Cell::new(&format!("  {}", price)), change_cell(0.0)),
+            Movement::New => (Cell::new(&format!("  {}", price)), Cell::new("")),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&format!(
+                "{}{}",
+                pair.base_token.symbol, pair.quote_token.symbol
+            )),
+            Cell::new(&pair.chain_id),
+            price_cell,
+            tick_cell,
+            change_cell(pair.price_change.m5),
+            change_cell(pair.price_change.h1),
+            Cell::new(&number::format_with_unit(pair.volume.h24)),
+        ]));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::honey_pair;
+    use crate::source::MockSource;
+
+    #[test]
+    fn test_movement_between() {
+        assert_eq!(Movement::between(None, Some(1.0)), Movement::New);
+        assert_eq!(Movement::between(Some(1.0), None), Movement::New);
+        assert_eq!(Movement::between(Some(1.0), Some(1.0)), Movement::Unchanged);
+        assert_eq!(Movement::between(Some(2.0), Some(3.0)), Movement::Up(50.0));
+        assert_eq!(
+            Movement::between(Some(2.0), Some(1.0)),
+            Movement::Down(-50.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watcher_ticks() {
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![honey_pair()],
+        });
+        let config = Config::default();
+        let mut watcher = Watcher::resolve(&client, &config, &["honey".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(watcher.pairs().len(), 1);
+
+        let rows = watcher.tick(&client).await;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].movement, Movement::New);
+
+        let rows = watcher.tick(&client).await;
+        assert_eq!(rows[0].movement, Movement::Unchanged);
+    }
+
+    #[tokio::test]
+    async fn test_failed_pair_keeps_its_price() {
+        let mut gone = PairConfig::from(honey_pair());
+        gone.pair_address = "gone".to_string();
+        let mut watcher = Watcher::new(vec![gone, PairConfig::from(honey_pair())]);
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![honey_pair()],
+        });
+        let rows = watcher.tick(&client).await;
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].pair.is_none());
+        assert!(rows[0].error.is_some());
+        assert!(watch_table(&rows).to_string().contains("Error: "));
+        assert_eq!(rows[1].movement, Movement::New);
+
+        // The honey pair fails on the next tick, so its price is kept.
+        let offline = TokenTide::with_source(MockSource { pairs: vec![] });
+        let rows = watcher.tick(&offline).await;
+        assert!(rows[1].error.is_some());
+        let mut cheaper = honey_pair();
+        cheaper.price_usd = Some("0.1".to_string());
+        let client = TokenTide::with_source(MockSource {
+            pairs: vec![cheaper],
+        });
+        let rows = watcher.tick(&client).await;
+        assert!(matches!(rows[1].movement, Movement::Down(_)));
+    }
+}
@@ -0,0 +1,456 @@
+//! End-to-end runs of the `tt` binary against the DexScreener stand-in, each
+//! with its own throwaway home directory.
+
+mod mock;
+
+use mock::MockDexScreener;
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+use std::time::{SystemTime, UNIX_EPOCH};
+use token_tide::config::CONFIG_ENV;
+use token_tide::dexscreener::HOST_ENV;
+use token_tide::profile::PROFILE_ENV;
+use token_tide::{DexScreener, PriceSource};
+use tokio::process::Command;
+
+const HONEY_RAYDIUM: &str = "2RVVkjA9cRHzZgpLiS1s5eRudqF8ZD3kguCGoU1vhjPo";
+const HONEY_ORCA: &str = "7NTkXFBMTM4y1DKfqeAmfKmcqXmkf2DzmAXXd4uqvQgd";
+const BONK_RAYDIUM: &str = "6qq2dSgcqdm7JPv8M3y6Yq8uCgbc4ZjczrJDcMTG1kkr";
+
+struct Home(PathBuf);
+
+impl Home {
+    fn new(name: &str) -> Home {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "token-tide-cli-{}-{}-{}",
+            name,
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        Home(dir)
+    }
+
+    fn config(&self) -> String {
+        fs::read_to_string(self.0.join(".config/token-tide/config.toml")).unwrap_or_default()
+    }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+async fn tt(mock: &MockDexScreener, home: &Home, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_tt"));
+    command
+        .args(args)
+        .env("HOME", &home.0)
+        .env(HOST_ENV, mock.url())
+        .env_remove(PROFILE_ENV);
+    for proxy in [
+        "HTTP_PROXY",
+        "HTTPS_PROXY",
+        "ALL_PROXY",
+        "http_proxy",
+        "https_proxy",
+        "all_proxy",
+    ] {
+        command.env_remove(proxy);
+    }
+    command.output().await.unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "tt failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn addresses(json: &str) -> Vec<String> {
+    let value: Value = serde_json::from_str(json).unwrap();
+    let pairs = match value {
+        Value::Array(pairs) => pairs,
+        pair => vec![pair],
+    };
+    pairs
+        .iter()
+        .map(|pair| pair["pairAddress"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_search() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("search");
+    let output = tt(&mock, &home, &["list", "honey", "--format", "json"]).await;
+    assert_eq!(addresses(&stdout(&output)), [HONEY_RAYDIUM, HONEY_ORCA]);
+    assert_eq!(mock.requests(), ["/latest/dex/search/?q=honey"]);
+    assert!(!home.config().contains(HONEY_RAYDIUM));
+}
+
+#[tokio::test]
+async fn test_query_pins_first_result() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("query");
+    let output = tt(&mock, &home, &["query", "honey", "--format", "json"]).await;
+    assert_eq!(addresses(&stdout(&output)), [HONEY_RAYDIUM]);
+    assert!(home.config().contains(HONEY_RAYDIUM));
+
+    // The pinned pair is fetched directly, without searching again.
+    let before = mock.requests().len();
+    let output = tt(&mock, &home, &["--no-cache", "query", "HONEY", "--simple"]).await;
+    assert!(stdout(&output).contains("0.1735"));
+    assert_eq!(
+        mock.requests()[before..],
+        [format!("/latest/dex/pairs/solana/{}", HONEY_RAYDIUM)]
+    );
+}
+
+#[tokio::test]
+async fn test_cache_hits_are_not_recorded() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("cache-history");
+    let history = || {
+        fs::read_to_string(home.0.join(".config/token-tide/history.jsonl"))
+            .unwrap_or_default()
+            .lines()
+            .count()
+    };
+    stdout(&tt(&mock, &home, &["query", "honey"]).await);
+    let recorded = history();
+    assert!(recorded > 0);
+    // Served from the cache, so nothing new is recorded.
+    stdout(&tt(&mock, &home, &["query", "honey"]).await);
+    stdout(&tt(&mock, &home, &["--offline", "query", "honey"]).await);
+    assert_eq!(history(), recorded);
+    stdout(&tt(&mock, &home, &["--refresh", "query", "honey"]).await);
+    assert_eq!(history(), recorded + 1);
+}
+
+#[tokio::test]
+async fn test_query_by_chain() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("query-chain");
+    let output = tt(&mock, &home, &["query", "solana:honey", "--format", "json"]).await;
+    assert_eq!(addresses(&stdout(&output)), [HONEY_RAYDIUM]);
+    assert!(mock
+        .requests()
+        .iter()
+        .any(|path| path.ends_with("?q=honey")));
+    let output = tt(&mock, &home, &["query", "base:honey"]).await;
+    assert_eq!(output.status.code(), Some(3));
+
+    // With two HONEY pools pinned, the symbol alone names neither.
+    stdout(&tt(&mock, &home, &["query", HONEY_ORCA]).await);
+    let output = tt(&mock, &home, &["query", "honey"]).await;
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("honey matches 2 pins"));
+    assert!(stderr.contains(HONEY_ORCA));
+}
+
+#[tokio::test]
+async fn test_query_many_batches_pairs() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("query-many");
+    let output = tt(
+        &mock,
+        &home,
+        &["query", "honey", "bonk", "--format", "json"],
+    )
+    .await;
+    assert_eq!(addresses(&stdout(&output)), [HONEY_RAYDIUM, BONK_RAYDIUM]);
+    let pairs: Vec<String> = mock
+        .requests()
+        .into_iter()
+        .filter(|path| path.starts_with("/latest/dex/pairs/"))
+        .collect();
+    assert_eq!(
+        pairs,
+        [format!(
+            "/latest/dex/pairs/solana/{},{}",
+            HONEY_RAYDIUM, BONK_RAYDIUM
+        )]
+    );
+}
+
+#[tokio::test]
+async fn test_add_replaces_pinned_pair() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("add");
+    stdout(&tt(&mock, &home, &["query", "honey"]).await);
+    let output = stdout(&tt(&mock, &home, &["add", HONEY_ORCA]).await);
+    assert!(output.contains(&format!("Before pair: {}", HONEY_RAYDIUM)));
+    assert!(output.contains(&format!("Appended HONEY. Now pair: {}", HONEY_ORCA)));
+    let config = home.config();
+    assert!(config.contains(HONEY_ORCA));
+    assert!(!config.contains(HONEY_RAYDIUM));
+}
+
+#[tokio::test]
+async fn test_sell_added_holding() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("sell-holding");
+    stdout(
+        &tt(
+            &mock,
+            &home,
+            &["portfolio", "add", "honey", "1000", "--cost", "150"],
+        )
+        .await,
+    );
+    let output = tt(
+        &mock,
+        &home,
+        &["portfolio", "sell", "honey", "10", "--price", "0.2"],
+    )
+    .await;
+    assert!(stdout(&output).contains("Recorded sell of 10 HONEY"));
+    let output = tt(&mock, &home, &["portfolio", "add", "honey", "0"]).await;
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[tokio::test]
+async fn test_config_location() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("config-location");
+    let custom = home.0.join("desk/tt.toml");
+    let flag = custom.to_str().unwrap();
+    stdout(&tt(&mock, &home, &["--config", flag, "query", "honey"]).await);
+    assert!(fs::read_to_string(&custom).unwrap().contains(HONEY_RAYDIUM));
+    assert!(home.0.join("desk/history.jsonl").exists());
+    assert_eq!(home.config(), "");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tt"))
+        .args(["query", "honey", "--simple"])
+        .env("HOME", &home.0)
+        .env(HOST_ENV, mock.url())
+        .env(CONFIG_ENV, &custom)
+        .env("XDG_CONFIG_HOME", home.0.join("xdg"))
+        .output()
+        .await
+        .unwrap();
+    assert!(stdout(&output).contains("0.1735"));
+    assert!(!home.0.join("xdg").exists());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tt"))
+        .args(["list", "honey"])
+        .env("HOME", &home.0)
+        .env(HOST_ENV, mock.url())
+        .env_remove(CONFIG_ENV)
+        .env("XDG_CONFIG_HOME", home.0.join("xdg"))
+        .output()
+        .await
+        .unwrap();
+    stdout(&output);
+    assert!(home.0.join("xdg/token-tide/config.toml").exists());
+    assert_eq!(home.config(), "");
+}
+
+#[tokio::test]
+async fn test_concurrent_processes_keep_every_pin() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("concurrent");
+    stdout(&tt(&mock, &home, &["alert", "list"]).await);
+    let runs = [
+        tt(&mock, &home, &["query", "honey"]),
+        tt(&mock, &home, &["query", "bonk"]),
+        tt(&mock, &home, &["add", HONEY_ORCA]),
+        tt(
+            &mock,
+            &home,
+            &["alert", "add", "bonk", "price_usd", "above", "1"],
+        ),
+    ];
+    for output in futures::future::join_all(runs).await {
+        stdout(&output);
+    }
+    let config = home.config();
+    assert!(config.contains(BONK_RAYDIUM));
+    assert!(config.contains(HONEY_ORCA) || config.contains(HONEY_RAYDIUM));
+    assert!(config.contains("[[alerts]]"));
+}
+
+#[tokio::test]
+async fn test_broken_config_is_kept() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("broken-config");
+    stdout(&tt(&mock, &home, &["query", "honey"]).await);
+    let path = home.0.join(".config/token-tide/config.toml");
+    let broken = format!("{}\nthreshold = 1.x\n", home.config());
+    fs::write(&path, &broken).unwrap();
+
+    let output = tt(&mock, &home, &["query", "honey"]).await;
+    assert_eq!(output.status.code(), Some(8));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("line "));
+    assert_eq!(home.config(), broken);
+
+    let output = tt(&mock, &home, &["config", "doctor"]).await;
+    assert_eq!(output.status.code(), Some(8));
+    let output = stdout(&tt(&mock, &home, &["config", "doctor", "--fix"]).await);
+    assert!(output.contains("Backed up"));
+    assert!(home.config().contains(HONEY_RAYDIUM));
+    let backups = fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with("config.toml.bak-")
+        })
+        .count();
+    assert_eq!(backups, 1);
+    stdout(&tt(&mock, &home, &["query", "honey"]).await);
+}
+
+#[tokio::test]
+async fn test_pins() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("pins");
+    stdout(&tt(&mock, &home, &["query", "honey", "bonk"]).await);
+    stdout(&tt(&mock, &home, &["query", HONEY_ORCA]).await);
+    let list = |output: &Output| -> Vec<Value> {
+        serde_json::from_str::<Vec<Value>>(&stdout(output)).unwrap()
+    };
+    let pins = list(&tt(&mock, &home, &["pins", "list", "--format", "json"]).await);
+    let order: Vec<&str> = pins
+        .iter()
+        .map(|pin| pin["pair_address"].as_str().unwrap())
+        .collect();
+    assert_eq!(order, [HONEY_RAYDIUM, BONK_RAYDIUM, HONEY_ORCA]);
+    assert_eq!(pins[0]["dex"], "raydium");
+
+    // Two pins share the symbol, so it cannot pick one.
+    let output = tt(&mock, &home, &["pins", "remove", "honey", "--yes"]).await;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("matches 2 pins"));
+
+    stdout(&tt(&mock, &home, &["pins", "move", "3", "1"]).await);
+    stdout(&tt(&mock, &home, &["pins", "rename", HONEY_ORCA, "honey-sol"]).await);
+    let output = tt(
+        &mock,
+        &home,
+        &["--no-cache", "query", "honey-sol", "--simple"],
+    )
+    .await;
+    assert!(stdout(&output).contains("0.1731"));
+
+    // Destructive commands are not run unasked without a terminal.
+    let output = tt(&mock, &home, &["pins", "remove", "2"]).await;
+    assert_eq!(output.status.code(), Some(2));
+    let output = stdout(&tt(&mock, &home, &["pins", "remove", "2", "--yes"]).await);
+    assert!(output.contains(&format!(
+        "Unpinned HONEY/USDC on solana ({})",
+        HONEY_RAYDIUM
+    )));
+    let pins = list(&tt(&mock, &home, &["pins", "list", "--format", "json"]).await);
+    let order: Vec<&str> = pins
+        .iter()
+        .map(|pin| pin["pair_address"].as_str().unwrap())
+        .collect();
+    assert_eq!(order, [HONEY_ORCA, BONK_RAYDIUM]);
+    assert_eq!(pins[0]["alias"], "honey-sol");
+
+    stdout(&tt(&mock, &home, &["pins", "clear", "-y"]).await);
+    let pins = list(&tt(&mock, &home, &["pins", "list", "--format", "json"]).await);
+    assert!(pins.is_empty());
+}
+
+#[tokio::test]
+async fn test_profiles() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("profiles");
+    stdout(&tt(&mock, &home, &["query", "honey"]).await);
+    stdout(
+        &tt(
+            &mock,
+            &home,
+            &["profile", "add", "trading", "--default-format", "json"],
+        )
+        .await,
+    );
+    // The profile has its own pins: bonk goes there, and honey resolves anew.
+    stdout(&tt(&mock, &home, &["--profile", "trading", "query", "bonk"]).await);
+    stdout(&tt(&mock, &home, &["--profile", "trading", "query", HONEY_ORCA]).await);
+
+    let pinned = |output: &Output| -> Vec<String> {
+        serde_json::from_str::<Vec<Value>>(&stdout(output))
+            .unwrap()
+            .iter()
+            .map(|pin| pin["pair_address"].as_str().unwrap().to_string())
+            .collect()
+    };
+    // No --format, so the profile's JSON is printed.
+    let pins = pinned(&tt(&mock, &home, &["--profile", "trading", "pins", "list"]).await);
+    assert_eq!(pins, [BONK_RAYDIUM, HONEY_ORCA]);
+    let pins = pinned(&tt(&mock, &home, &["pins", "list", "--format", "json"]).await);
+    assert_eq!(pins, [HONEY_RAYDIUM]);
+    let output = tt(
+        &mock,
+        &home,
+        &["--profile", "trading", "pins", "list", "--format", "table"],
+    )
+    .await;
+    assert!(stdout(&output).contains("| # |"));
+
+    // The profile's pin wins over the search results.
+    let output = tt(
+        &mock,
+        &home,
+        &[
+            "--profile",
+            "trading",
+            "--no-cache",
+            "query",
+            "honey",
+            "--simple",
+        ],
+    )
+    .await;
+    assert!(stdout(&output).contains("0.1731"));
+    assert!(home.config().contains("[profiles.trading]"));
+
+    let output = tt(&mock, &home, &["--profile", "missing", "pins", "list"]).await;
+    assert_eq!(output.status.code(), Some(3));
+    stdout(&tt(&mock, &home, &["profile", "remove", "trading", "--yes"]).await);
+    let output = tt(&mock, &home, &["--profile", "trading", "pins", "list"]).await;
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[tokio::test]
+async fn test_not_found() {
+    let mock = MockDexScreener::start().await;
+    let home = Home::new("not-found");
+    let output = tt(&mock, &home, &["query", "nothing"]).await;
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("nothing"));
+}
+
+#[tokio::test]
+async fn test_library_against_stand_in() {
+    let mock = MockDexScreener::start().await;
+    let source = DexScreener::new().with_host(mock.url());
+    let pairs = source
+        .fetch_pairs(
+            "solana",
+            &[HONEY_ORCA.to_string(), BONK_RAYDIUM.to_string()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(pairs.len(), 2);
+    let tokens = source
+        .fetch_token_pairs("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263")
+        .await
+        .unwrap();
+    assert_eq!(tokens[0].pair_address, BONK_RAYDIUM);
+    assert_eq!(mock.requests().len(), 2);
+}
@@ -0,0 +1,145 @@
+//! A stand-in for the DexScreener API serving the recorded responses in
+//! `tests/fixtures`, so the integration tests never touch the network.
+//!
+//! `search_<query>.json` files are served verbatim for that search. Every
+//! pair found in any fixture also answers the pairs and tokens endpoints and
+//! searches by pair or token address, like the real API does.
+
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+pub struct MockDexScreener {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockDexScreener {
+    /// Starts serving on a free local port until the runtime shuts down.
+    pub async fn start() -> MockDexScreener {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, log.clone()));
+            }
+        });
+        MockDexScreener { url, requests }
+    }
+
+    /// The base URL to hand to `DexScreener::with_host` or `TOKEN_TIDE_API_URL`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The paths requested so far, in arrival order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(mut socket: TcpStream, log: Arc<Mutex<Vec<String>>>) {
+    let mut request = vec![];
+    let mut buf = [0; 4096];
+    while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+        match socket.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => request.extend_from_slice(&buf[..read]),
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+    log.lock().unwrap().push(path.clone());
+    let (status, body) = match respond(&path) {
+        Some(body) => ("200 OK", body),
+        None => ("404 Not Found", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = socket.write_all(response.as_bytes()).await;
+}
+
+/// The body for a request path, `None` for paths the API does not serve.
+fn respond(path: &str) -> Option<String> {
+    if let Some(query) = path
+        .strip_prefix("/latest/dex/search/?q=")
+        .or_else(|| path.strip_prefix("/latest/dex/search?q="))
+    {
+        let recorded = Path::new(FIXTURES).join(format!("search_{}.json", query.to_lowercase()));
+        if let Ok(body) = fs::read_to_string(recorded) {
+            return Some(body);
+        }
+        return Some(pairs_body(known_pairs().into_iter().filter(|pair| {
+            field(pair, "/pairAddress").eq_ignore_ascii_case(query)
+                || field(pair, "/baseToken/address").eq_ignore_ascii_case(query)
+        })));
+    }
+    if let Some(rest) = path.strip_prefix("/latest/dex/pairs/") {
+        let (chain_id, addresses) = rest.split_once('/')?;
+        let addresses: Vec<&str> = addresses.split(',').collect();
+        return Some(pairs_body(known_pairs().into_iter().filter(|pair| {
+            field(pair, "/chainId") == chain_id
+                && addresses.contains(&field(pair, "/pairAddress").as_str())
+        })));
+    }
+    if let Some(addresses) = path.strip_prefix("/latest/dex/tokens/") {
+        let addresses: Vec<&str> = addresses.split(',').collect();
+        return Some(pairs_body(known_pairs().into_iter().filter(|pair| {
+            addresses.contains(&field(pair, "/baseToken/address").as_str())
+        })));
+    }
+    None
+}
+
+/// Every pair in the fixtures, once per chain and address.
+fn known_pairs() -> Vec<Value> {
+    let mut files: Vec<_> = fs::read_dir(FIXTURES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    let mut pairs: Vec<Value> = vec![];
+    for file in files {
+        let body: Value = serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        for pair in body["pairs"].as_array().into_iter().flatten() {
+            let seen = pairs.iter().any(|known| {
+                field(known, "/chainId") == field(pair, "/chainId")
+                    && field(known, "/pairAddress") == field(pair, "/pairAddress")
+            });
+            if !seen {
+                pairs.push(pair.clone());
+            }
+        }
+    }
+    pairs
+}
+
+fn field(pair: &Value, pointer: &str) -> String {
+    pair.pointer(pointer)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Answers like the API, with `"pairs": null` when nothing matches.
+fn pairs_body(pairs: impl Iterator<Item = Value>) -> String {
+    let pairs: Vec<Value> = pairs.collect();
+    let pairs = if pairs.is_empty() {
+        Value::Null
+    } else {
+        Value::Array(pairs)
+    };
+    json!({ "schemaVersion": "1.0.0", "pairs": pairs }).to_string()
+}